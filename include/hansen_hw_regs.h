@@ -19,10 +19,16 @@
 #define REG_DMA_DST 0x04
 #define REG_DMA_LEN 0x08
 #define REG_DMA_CTRL 0x0C
+#define REG_DMA_STATUS 0x10
 
 // --- DMA Control Bits ---
 #define DMA_CTRL_START (1 << 0)
 #define DMA_CTRL_IRQ_EN (1 << 1)
+#define DMA_CTRL_DIR (1 << 2) // 1 = Device SRAM -> Host
+
+// --- DMA Status Bits ---
+#define DMA_STATUS_BUSY (1 << 0)
+#define DMA_STATUS_ERROR (1 << 1)
 
 // --- Interrupts ---
 #define IRQ_DMA_COMPLETE 0x1
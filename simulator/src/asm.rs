@@ -0,0 +1,742 @@
+
+// Assembler
+// Two-pass assembler for Hansen (RV32IM + HALT) text assembly.
+//
+// Syntax:
+//   label:  mnemonic operand, operand, ...   # comment (also // and ;)
+// Directives: .text, .data, .org <addr>, .equ <name>, <expr>, .word <expr>, ...
+// Pseudo-instructions: nop, li, la, mv, not, neg, j, jr, ret, call, beqz, bnez,
+//                      csrr, csrw, csrs, csrc
+// CSR operands are either a CSR name (mstatus, mcycle, ...) or an expression.
+//
+// The .text section starts at address 0. The .data section is placed right
+// after the end of .text (word aligned) unless it is positioned with .org.
+// The image must fit in device SRAM (MAX_IMAGE_SIZE bytes from address 0).
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::csr::csr_address;
+use crate::isa::{DecodeError, Instruction, Opcode, ABI_NAMES};
+
+pub const MAX_IMAGE_SIZE: u32 = 0x1_0000; // 64KB device SRAM
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AsmError {
+    pub line: usize, // 1-based source line
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+#[derive(Debug)]
+pub struct Program {
+    pub image: Vec<u8>, // Memory image starting at address 0
+    pub symbols: HashMap<String, u32>,
+}
+
+impl Program {
+    // The image as little-endian words, zero-padded to a word boundary
+    pub fn words(&self) -> Vec<u32> {
+        self.image
+            .chunks(4)
+            .map(|c| c.iter().enumerate().fold(0u32, |w, (i, &b)| w | (b as u32) << (8 * i)))
+            .collect()
+    }
+
+    // Decodes the whole image; fails if it contains data words
+    pub fn instructions(&self) -> Result<Vec<Instruction>, DecodeError> {
+        self.words().into_iter().map(Instruction::decode).collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Section {
+    Text,
+    Data,
+}
+
+// A location counter. Data offsets stay relative to the (not yet known)
+// start of .data until a .org makes them absolute.
+#[derive(Debug, Clone, Copy)]
+struct Loc {
+    offset: u32,
+    absolute: bool,
+}
+
+enum ItemKind {
+    Instr { mnemonic: String, operands: Vec<String> },
+    Words(Vec<String>),
+}
+
+struct Item {
+    line: usize,
+    loc: Loc,
+    size: u32,
+    kind: ItemKind,
+}
+
+enum Symbol {
+    Label(Loc),
+    Const(i64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    R,
+    I,
+    Shift,
+    Load,
+    Store,
+    Branch,
+    Jal,
+    Jalr,
+    U,
+    Fence,
+    Csr,
+    CsrImm,
+    NoOperands,
+}
+
+fn format_of(opcode: Opcode) -> Format {
+    match opcode {
+        Opcode::ADD | Opcode::SUB | Opcode::SLL | Opcode::SLT | Opcode::SLTU | Opcode::XOR
+        | Opcode::SRL | Opcode::SRA | Opcode::OR | Opcode::AND | Opcode::MUL | Opcode::MULH
+        | Opcode::MULHSU | Opcode::MULHU | Opcode::DIV | Opcode::DIVU | Opcode::REM
+        | Opcode::REMU => Format::R,
+        Opcode::ADDI | Opcode::SLTI | Opcode::SLTIU | Opcode::XORI | Opcode::ORI
+        | Opcode::ANDI => Format::I,
+        Opcode::SLLI | Opcode::SRLI | Opcode::SRAI => Format::Shift,
+        Opcode::LB | Opcode::LH | Opcode::LW | Opcode::LBU | Opcode::LHU => Format::Load,
+        Opcode::SB | Opcode::SH | Opcode::SW => Format::Store,
+        Opcode::BEQ | Opcode::BNE | Opcode::BLT | Opcode::BGE | Opcode::BLTU
+        | Opcode::BGEU => Format::Branch,
+        Opcode::JAL => Format::Jal,
+        Opcode::JALR => Format::Jalr,
+        Opcode::LUI | Opcode::AUIPC => Format::U,
+        Opcode::FENCE => Format::Fence,
+        Opcode::CSRRW | Opcode::CSRRS | Opcode::CSRRC => Format::Csr,
+        Opcode::CSRRWI | Opcode::CSRRSI | Opcode::CSRRCI => Format::CsrImm,
+        Opcode::ECALL | Opcode::EBREAK | Opcode::MRET | Opcode::HALT => Format::NoOperands,
+    }
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut end = line.len();
+    for pat in ["#", "//", ";"] {
+        if let Some(i) = line.find(pat) {
+            end = end.min(i);
+        }
+    }
+    &line[..end]
+}
+
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' || c == '.' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+fn split_operands(text: &str) -> Vec<String> {
+    if text.trim().is_empty() {
+        return Vec::new();
+    }
+    text.split(',').map(|s| s.trim().to_string()).collect()
+}
+
+pub fn parse_register(name: &str) -> Result<usize, String> {
+    let name = name.trim().to_ascii_lowercase();
+    if let Some(num) = name.strip_prefix('x') {
+        if let Ok(idx) = num.parse::<usize>() {
+            if idx < 32 {
+                return Ok(idx);
+            }
+        }
+    }
+    if name == "fp" {
+        return Ok(8);
+    }
+    ABI_NAMES
+        .iter()
+        .position(|&abi| abi == name)
+        .ok_or_else(|| format!("invalid register '{}'", name))
+}
+
+fn parse_number(text: &str) -> Option<i64> {
+    let (neg, digits) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if let Some(bin) = digits.strip_prefix("0b").or_else(|| digits.strip_prefix("0B")) {
+        i64::from_str_radix(bin, 2).ok()?
+    } else {
+        digits.parse::<i64>().ok()?
+    };
+    Some(if neg { -value } else { value })
+}
+
+// Upper/lower split such that (hi << 12) + lo == value with lo in [-2048, 2047]
+fn split_hi_lo(value: i32) -> (i32, i32) {
+    let lo = ((value & 0xFFF) ^ 0x800) - 0x800;
+    let hi = value.wrapping_sub(lo) & !0xFFF;
+    (hi, lo)
+}
+
+// Evaluates `term (+|- term)*` where a term is a number, a symbol,
+// %hi(expr) or %lo(expr).
+fn eval(expr: &str, lookup: &dyn Fn(&str) -> Option<i64>) -> Result<i64, String> {
+    let expr = expr.trim();
+    if expr.is_empty() {
+        return Err("missing expression".to_string());
+    }
+    if let Some(inner) = expr.strip_prefix("%hi(").and_then(|e| e.strip_suffix(')')) {
+        let value = eval(inner, lookup)? as i32;
+        return Ok(((split_hi_lo(value).0 as u32) >> 12) as i64);
+    }
+    if let Some(inner) = expr.strip_prefix("%lo(").and_then(|e| e.strip_suffix(')')) {
+        let value = eval(inner, lookup)? as i32;
+        return Ok(split_hi_lo(value).1 as i64);
+    }
+
+    let mut total = 0i64;
+    let mut sign = 1i64;
+    let mut term = String::new();
+    let flush = |term: &mut String, sign: i64, total: &mut i64| -> Result<(), String> {
+        let t = term.trim();
+        if t.is_empty() {
+            return Err(format!("malformed expression '{}'", expr));
+        }
+        let value = match parse_number(t) {
+            Some(v) => v,
+            None if is_identifier(t) => lookup(t).ok_or_else(|| format!("undefined symbol '{}'", t))?,
+            None => return Err(format!("invalid number '{}'", t)),
+        };
+        *total = sign
+            .checked_mul(value)
+            .and_then(|v| total.checked_add(v))
+            .ok_or_else(|| format!("immediate out of range in '{}'", expr))?;
+        term.clear();
+        Ok(())
+    };
+    for (i, c) in expr.char_indices() {
+        if (c == '+' || c == '-') && !term.trim().is_empty() {
+            flush(&mut term, sign, &mut total)?;
+            sign = if c == '+' { 1 } else { -1 };
+        } else if c == '-' && i == 0 {
+            sign = -1;
+        } else {
+            term.push(c);
+        }
+    }
+    flush(&mut term, sign, &mut total)?;
+    Ok(total)
+}
+
+// "imm(reg)" or "(reg)"
+fn parse_mem_operand(text: &str) -> Result<(String, usize), String> {
+    let open = text.find('(').ok_or_else(|| format!("expected offset(register), found '{}'", text))?;
+    let close = text.rfind(')').filter(|&c| c > open).ok_or_else(|| format!("missing ')' in '{}'", text))?;
+    let offset = text[..open].trim();
+    let reg = parse_register(&text[open + 1..close])?;
+    Ok((if offset.is_empty() { "0".to_string() } else { offset.to_string() }, reg))
+}
+
+fn parse_fence_set(text: &str) -> Result<i32, String> {
+    if text == "0" {
+        return Ok(0);
+    }
+    let mut bits = 0;
+    for c in text.chars() {
+        bits |= match c {
+            'i' => 8,
+            'o' => 4,
+            'r' => 2,
+            'w' => 1,
+            _ => return Err(format!("invalid fence set '{}'", text)),
+        };
+    }
+    Ok(bits)
+}
+
+fn parse_csr(text: &str, lookup: &dyn Fn(&str) -> Option<i64>) -> Result<i32, String> {
+    if let Some(addr) = csr_address(&text.to_ascii_lowercase()) {
+        return Ok(addr as i32);
+    }
+    let addr = eval(text, lookup)?;
+    if !(0..=0xFFF).contains(&addr) {
+        return Err(format!("CSR number {} is out of range [0, 0xfff]", addr));
+    }
+    Ok(addr as i32)
+}
+
+fn expect_operands(mnemonic: &str, operands: &[String], count: usize) -> Result<(), String> {
+    if operands.len() != count {
+        return Err(format!("'{}' expects {} operand(s), found {}", mnemonic, count, operands.len()));
+    }
+    Ok(())
+}
+
+fn to_i32(value: i64) -> Result<i32, String> {
+    if value < i32::MIN as i64 || value > u32::MAX as i64 {
+        return Err(format!("value {} does not fit in 32 bits", value));
+    }
+    Ok(value as i32)
+}
+
+// Size in bytes of an instruction statement, decided during pass 1.
+// `li` needs two words unless its value is already known to fit in 12 bits.
+fn instr_size(mnemonic: &str, operands: &[String], lookup: &dyn Fn(&str) -> Option<i64>) -> u32 {
+    match mnemonic {
+        "la" => 8,
+        "li" => match operands.get(1).map(|e| eval(e, lookup)) {
+            Some(Ok(v)) if (-2048..=2047).contains(&v) => 4,
+            _ => 8,
+        },
+        _ => 4,
+    }
+}
+
+// Expands one (pseudo-)instruction at `pc` into real instructions
+fn expand(
+    mnemonic: &str,
+    operands: &[String],
+    pc: u32,
+    size: u32,
+    lookup: &dyn Fn(&str) -> Option<i64>,
+) -> Result<Vec<Instruction>, String> {
+    let reg = |i: usize| parse_register(&operands[i]);
+    let value = |i: usize| eval(&operands[i], lookup).and_then(to_i32);
+    let offset_to = |i: usize| value(i).map(|target| target.wrapping_sub(pc as i32));
+    let csr = |i: usize| parse_csr(&operands[i], lookup);
+
+    let instrs = match mnemonic {
+        "nop" => {
+            expect_operands(mnemonic, operands, 0)?;
+            vec![Instruction::new_i_type(Opcode::ADDI, 0, 0, 0)]
+        }
+        "li" | "la" => {
+            expect_operands(mnemonic, operands, 2)?;
+            let rd = reg(0)?;
+            let v = value(1)?;
+            if size == 4 {
+                vec![Instruction::new_i_type(Opcode::ADDI, rd, 0, v)]
+            } else {
+                let (hi, lo) = split_hi_lo(v);
+                vec![
+                    Instruction::new_u_type(Opcode::LUI, rd, hi),
+                    Instruction::new_i_type(Opcode::ADDI, rd, rd, lo),
+                ]
+            }
+        }
+        "mv" => {
+            expect_operands(mnemonic, operands, 2)?;
+            vec![Instruction::new_i_type(Opcode::ADDI, reg(0)?, reg(1)?, 0)]
+        }
+        "not" => {
+            expect_operands(mnemonic, operands, 2)?;
+            vec![Instruction::new_i_type(Opcode::XORI, reg(0)?, reg(1)?, -1)]
+        }
+        "neg" => {
+            expect_operands(mnemonic, operands, 2)?;
+            vec![Instruction::new_r_type(Opcode::SUB, reg(0)?, 0, reg(1)?)]
+        }
+        "j" => {
+            expect_operands(mnemonic, operands, 1)?;
+            vec![Instruction::new_j_type(Opcode::JAL, 0, offset_to(0)?)]
+        }
+        "call" => {
+            expect_operands(mnemonic, operands, 1)?;
+            vec![Instruction::new_j_type(Opcode::JAL, 1, offset_to(0)?)]
+        }
+        "jr" => {
+            expect_operands(mnemonic, operands, 1)?;
+            vec![Instruction::new_i_type(Opcode::JALR, 0, reg(0)?, 0)]
+        }
+        "ret" => {
+            expect_operands(mnemonic, operands, 0)?;
+            vec![Instruction::new_i_type(Opcode::JALR, 0, 1, 0)]
+        }
+        "beqz" | "bnez" => {
+            expect_operands(mnemonic, operands, 2)?;
+            let op = if mnemonic == "beqz" { Opcode::BEQ } else { Opcode::BNE };
+            vec![Instruction::new_b_type(op, reg(0)?, 0, offset_to(1)?)]
+        }
+        "csrr" => {
+            expect_operands(mnemonic, operands, 2)?;
+            vec![Instruction::new_i_type(Opcode::CSRRS, reg(0)?, 0, csr(1)?)]
+        }
+        "csrw" | "csrs" | "csrc" => {
+            expect_operands(mnemonic, operands, 2)?;
+            let op = match mnemonic {
+                "csrw" => Opcode::CSRRW,
+                "csrs" => Opcode::CSRRS,
+                _ => Opcode::CSRRC,
+            };
+            vec![Instruction::new_i_type(op, 0, reg(1)?, csr(0)?)]
+        }
+        _ => {
+            let opcode = Opcode::from_mnemonic(mnemonic)
+                .ok_or_else(|| format!("unknown instruction '{}'", mnemonic))?;
+            let instr = match format_of(opcode) {
+                Format::R => {
+                    expect_operands(mnemonic, operands, 3)?;
+                    Instruction::new_r_type(opcode, reg(0)?, reg(1)?, reg(2)?)
+                }
+                Format::I | Format::Shift => {
+                    expect_operands(mnemonic, operands, 3)?;
+                    Instruction::new_i_type(opcode, reg(0)?, reg(1)?, value(2)?)
+                }
+                Format::Load => {
+                    expect_operands(mnemonic, operands, 2)?;
+                    let (offset, base) = parse_mem_operand(&operands[1])?;
+                    let imm = eval(&offset, lookup).and_then(to_i32)?;
+                    Instruction::new_i_type(opcode, reg(0)?, base, imm)
+                }
+                Format::Store => {
+                    expect_operands(mnemonic, operands, 2)?;
+                    let (offset, base) = parse_mem_operand(&operands[1])?;
+                    let imm = eval(&offset, lookup).and_then(to_i32)?;
+                    Instruction::new_s_type(opcode, base, reg(0)?, imm)
+                }
+                Format::Branch => {
+                    expect_operands(mnemonic, operands, 3)?;
+                    Instruction::new_b_type(opcode, reg(0)?, reg(1)?, offset_to(2)?)
+                }
+                Format::Jal => match operands.len() {
+                    1 => Instruction::new_j_type(opcode, 1, offset_to(0)?),
+                    _ => {
+                        expect_operands(mnemonic, operands, 2)?;
+                        Instruction::new_j_type(opcode, reg(0)?, offset_to(1)?)
+                    }
+                },
+                Format::Jalr => match operands.len() {
+                    1 => Instruction::new_i_type(opcode, 1, reg(0)?, 0),
+                    2 => {
+                        let (offset, base) = parse_mem_operand(&operands[1])?;
+                        let imm = eval(&offset, lookup).and_then(to_i32)?;
+                        Instruction::new_i_type(opcode, reg(0)?, base, imm)
+                    }
+                    _ => {
+                        expect_operands(mnemonic, operands, 3)?;
+                        Instruction::new_i_type(opcode, reg(0)?, reg(1)?, value(2)?)
+                    }
+                },
+                Format::U => {
+                    expect_operands(mnemonic, operands, 2)?;
+                    let upper = value(1)?;
+                    if !(0..=0xFFFFF).contains(&upper) {
+                        return Err(format!("upper immediate {} is out of range [0, 0xfffff]", upper));
+                    }
+                    Instruction::new_u_type(opcode, reg(0)?, upper << 12)
+                }
+                Format::Fence => match operands.len() {
+                    0 => Instruction::new_i_type(opcode, 0, 0, 0xFF),
+                    _ => {
+                        expect_operands(mnemonic, operands, 2)?;
+                        let pred = parse_fence_set(&operands[0])?;
+                        let succ = parse_fence_set(&operands[1])?;
+                        Instruction::new_i_type(opcode, 0, 0, (pred << 4) | succ)
+                    }
+                },
+                Format::Csr => {
+                    expect_operands(mnemonic, operands, 3)?;
+                    Instruction::new_i_type(opcode, reg(0)?, reg(2)?, csr(1)?)
+                }
+                Format::CsrImm => {
+                    expect_operands(mnemonic, operands, 3)?;
+                    let zimm = value(2)?;
+                    if !(0..=31).contains(&zimm) {
+                        return Err(format!("CSR immediate {} is out of range [0, 31]", zimm));
+                    }
+                    Instruction::new_i_type(opcode, reg(0)?, zimm as usize, csr(1)?)
+                }
+                Format::NoOperands => {
+                    expect_operands(mnemonic, operands, 0)?;
+                    let imm = match opcode {
+                        Opcode::EBREAK => 1,
+                        Opcode::MRET => 0x302,
+                        _ => 0,
+                    };
+                    Instruction::new_i_type(opcode, 0, 0, imm)
+                }
+            };
+            vec![instr]
+        }
+    };
+    debug_assert_eq!(instrs.len() as u32 * 4, size);
+    Ok(instrs)
+}
+
+pub fn assemble(source: &str) -> Result<Program, AsmError> {
+    let mut symbols: HashMap<String, Symbol> = HashMap::new();
+    let mut items: Vec<Item> = Vec::new();
+    let mut section = Section::Text;
+    let mut text_loc = Loc { offset: 0, absolute: true };
+    let mut data_loc = Loc { offset: 0, absolute: false };
+    let mut text_end = 0u32;
+
+    // Pass 1: layout, labels and constants
+    for (idx, raw) in source.lines().enumerate() {
+        let line = idx + 1;
+        let err = |message: String| AsmError { line, message };
+        let mut text = strip_comment(raw).trim();
+
+        let loc = match section {
+            Section::Text => &mut text_loc,
+            Section::Data => &mut data_loc,
+        };
+
+        while let Some(colon) = text.find(':') {
+            let name = text[..colon].trim();
+            if !is_identifier(name) {
+                break;
+            }
+            if symbols.contains_key(name) {
+                return Err(err(format!("duplicate symbol '{}'", name)));
+            }
+            symbols.insert(name.to_string(), Symbol::Label(*loc));
+            text = text[colon + 1..].trim();
+        }
+        if text.is_empty() {
+            continue;
+        }
+
+        let (head, rest) = match text.find(char::is_whitespace) {
+            Some(i) => (&text[..i], text[i..].trim()),
+            None => (text, ""),
+        };
+        let operands = split_operands(rest);
+        let lookup_pass1 = |name: &str| match symbols.get(name) {
+            Some(Symbol::Const(v)) => Some(*v),
+            Some(Symbol::Label(l)) if l.absolute => Some(l.offset as i64),
+            _ => None,
+        };
+
+        let (size, kind) = match head.to_ascii_lowercase().as_str() {
+            ".text" => {
+                section = Section::Text;
+                continue;
+            }
+            ".data" => {
+                section = Section::Data;
+                continue;
+            }
+            ".globl" | ".global" | ".section" => continue,
+            ".org" => {
+                expect_operands(head, &operands, 1).map_err(err)?;
+                let addr = eval(&operands[0], &lookup_pass1).map_err(err)?;
+                if !(0..=MAX_IMAGE_SIZE as i64).contains(&addr) {
+                    return Err(err(format!(".org address {} is out of range", addr)));
+                }
+                *loc = Loc { offset: addr as u32, absolute: true };
+                continue;
+            }
+            ".equ" | ".set" => {
+                expect_operands(head, &operands, 2).map_err(err)?;
+                let name = operands[0].as_str();
+                if !is_identifier(name) {
+                    return Err(err(format!("invalid symbol name '{}'", name)));
+                }
+                if symbols.contains_key(name) {
+                    return Err(err(format!("duplicate symbol '{}'", name)));
+                }
+                let value = eval(&operands[1], &lookup_pass1).map_err(err)?;
+                symbols.insert(name.to_string(), Symbol::Const(value));
+                continue;
+            }
+            ".word" => {
+                if operands.is_empty() {
+                    return Err(err(".word expects at least one value".to_string()));
+                }
+                (4 * operands.len() as u32, ItemKind::Words(operands))
+            }
+            directive if directive.starts_with('.') => {
+                return Err(err(format!("unknown directive '{}'", directive)));
+            }
+            mnemonic => {
+                let size = instr_size(mnemonic, &operands, &lookup_pass1);
+                (size, ItemKind::Instr { mnemonic: mnemonic.to_string(), operands })
+            }
+        };
+
+        if loc.offset % 4 != 0 {
+            return Err(err(format!("location 0x{:x} is not word aligned", loc.offset)));
+        }
+        items.push(Item { line, loc: *loc, size, kind });
+        loc.offset = loc.offset.checked_add(size).ok_or_else(|| err("location counter overflow".to_string()))?;
+        if section == Section::Text {
+            text_end = text_end.max(loc.offset);
+        }
+    }
+
+    // Pass 2: resolve addresses and emit
+    let data_base = (text_end + 3) & !3;
+    // None once a relative location would overflow the address space
+    let resolve = |loc: Loc| if loc.absolute { Some(loc.offset) } else { data_base.checked_add(loc.offset) };
+    let lookup = |name: &str| match symbols.get(name) {
+        Some(Symbol::Const(v)) => Some(*v),
+        Some(Symbol::Label(l)) => resolve(*l).map(|addr| addr as i64),
+        None => None,
+    };
+
+    let mut image_len = 0;
+    for item in &items {
+        match resolve(item.loc).and_then(|addr| addr.checked_add(item.size)) {
+            Some(end) if end <= MAX_IMAGE_SIZE => image_len = image_len.max(end as usize),
+            _ => {
+                let message = format!("output extends past the end of device SRAM (0x{:x})", MAX_IMAGE_SIZE);
+                return Err(AsmError { line: item.line, message });
+            }
+        }
+    }
+    let mut image = vec![0u8; image_len];
+    let mut written = vec![false; image_len];
+
+    for item in &items {
+        let err = |message: String| AsmError { line: item.line, message };
+        let addr = resolve(item.loc).expect("items lie within the image");
+        let words: Vec<u32> = match &item.kind {
+            ItemKind::Words(exprs) => exprs
+                .iter()
+                .map(|e| eval(e, &lookup).and_then(to_i32).map(|v| v as u32))
+                .collect::<Result<_, _>>()
+                .map_err(err)?,
+            ItemKind::Instr { mnemonic, operands } => expand(mnemonic, operands, addr, item.size, &lookup)
+                .map_err(err)?
+                .iter()
+                .map(|i| i.encode().map_err(|e| e.to_string()))
+                .collect::<Result<_, _>>()
+                .map_err(err)?,
+        };
+        for (i, word) in words.iter().enumerate() {
+            let at = addr as usize + 4 * i;
+            if written[at..at + 4].iter().any(|&w| w) {
+                return Err(err(format!("output overlaps existing data at 0x{:x}", at)));
+            }
+            image[at..at + 4].copy_from_slice(&word.to_le_bytes());
+            written[at..at + 4].fill(true);
+        }
+    }
+
+    let symbols = symbols
+        .iter()
+        .map(|(name, sym)| {
+            let value = match sym {
+                Symbol::Const(v) => *v as u32,
+                Symbol::Label(l) => resolve(*l).expect("labels lie within the image"),
+            };
+            (name.clone(), value)
+        })
+        .collect();
+    Ok(Program { image, symbols })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assemble_labels_and_pseudos() {
+        let src = "
+            .equ COUNT, 10
+            start:  li   x3, COUNT       # x3 = 10
+                    li   x6, 0x12345678
+                    mv   x4, zero
+            loop:   beq  x4, x3, end
+                    addi x4, x4, 1
+                    j    loop
+            end:    halt
+        ";
+        let program = assemble(src).unwrap();
+        let text: Vec<String> = program.instructions().unwrap().iter().map(|i| i.to_string()).collect();
+        assert_eq!(text, [
+            "addi x3, x0, 10",
+            "lui x6, 0x12345",
+            "addi x6, x6, 1656",
+            "addi x4, x0, 0",
+            "beq x4, x3, 12",
+            "addi x4, x4, 1",
+            "jal x0, -8",
+            "halt",
+        ]);
+        assert_eq!(program.symbols["loop"], 16);
+        assert_eq!(program.symbols["end"], 28);
+    }
+
+    #[test]
+    fn test_data_section_and_org() {
+        let src = "
+            la   a0, table
+            lw   a1, 4(a0)
+            halt
+            .data
+            table: .word 1, -2, 0xdeadbeef
+            .org 0x40
+            marker: .word table
+        ";
+        let program = assemble(src).unwrap();
+        // 3 text words (la is two), so .data starts at 16
+        assert_eq!(program.symbols["table"], 16);
+        assert_eq!(program.symbols["marker"], 0x40);
+        let words = program.words();
+        assert_eq!(&words[4..7], &[1, (-2i32) as u32, 0xdeadbeef]);
+        assert_eq!(words[0x40 / 4], 16);
+    }
+
+    #[test]
+    fn test_errors_report_line_numbers() {
+        let err = assemble("nop\n  addi x1, x2\n").unwrap_err();
+        assert_eq!(err.line, 2);
+        assert!(err.message.contains("expects 3"));
+
+        let err = assemble("nop\nnop\n  beq x1, x2, missing\n").unwrap_err();
+        assert_eq!(err, AsmError { line: 3, message: "undefined symbol 'missing'".to_string() });
+
+        let err = assemble("addi x1, x0, 4096").unwrap_err();
+        assert_eq!(err.line, 1);
+        assert!(err.message.contains("out of range"));
+
+        assert_eq!(assemble("a:\na: nop").unwrap_err().line, 2);
+        assert_eq!(assemble("nop\n.org 0\nnop").unwrap_err().line, 3);
+        assert_eq!(assemble(".org 0x7ffffff0\nhalt").unwrap_err().line, 1);
+        assert_eq!(assemble(".org 0xfffc\nnop\nnop").unwrap_err().line, 3);
+        assert_eq!(assemble(".data\n.word 1\n.text\n.org 0xfff8\nnop\nnop").unwrap_err().line, 2);
+        assert_eq!(assemble("frob x1").unwrap_err().message, "unknown instruction 'frob'");
+        assert!(assemble("csrrwi x1, mstatus, 32").unwrap_err().message.contains("out of range"));
+        assert!(assemble("csrr x1, 0x1000").unwrap_err().message.contains("out of range"));
+        let err = assemble("nop\nli a0, 0x7fffffffffffffff+1").unwrap_err();
+        assert_eq!(err, AsmError { line: 2, message: "immediate out of range in '0x7fffffffffffffff+1'".to_string() });
+        assert_eq!(assemble("li a0, -0x7fffffffffffffff-2").unwrap_err().line, 1);
+    }
+
+    #[test]
+    fn test_csr_instructions() {
+        let src = "
+            csrr   t0, mhartid
+            csrw   mtvec, t0
+            csrrsi x0, MSTATUS, 8
+            csrrc  a0, 0x7c0, a1
+            mret
+        ";
+        let program = assemble(src).unwrap();
+        let text: Vec<String> = program.instructions().unwrap().iter().map(|i| i.to_string()).collect();
+        assert_eq!(text, [
+            "csrrs x5, mhartid, x0",
+            "csrrw x0, mtvec, x5",
+            "csrrsi x0, mstatus, 8",
+            "csrrc x10, 0x7c0, x11",
+            "mret",
+        ]);
+    }
+}
@@ -26,10 +26,6 @@ fn main() {
 
     println!("--- Micro-Benchmark: Driver Overhead (N={} Instructions) ---", iters);
 
-    // 1. Raw Core Execution (Simulating "Bare Metal")
-    let mut core = Core::new(0);
-    let mut mem = Memory::new(65536, 0); // 0 latency for pure logic bench
-    
     // 1. Raw Core Execution (Simulating "Bare Metal")
     // Manually iterate check
     let start_raw = Instant::now();
@@ -1,10 +1,7 @@
 use std::env;
-use std::fs::File;
-use std::io::{BufRead, BufReader};
-use std::path::Path;
 use simulator::core::Core;
 use simulator::memory::Memory;
-use simulator::isa::Instruction;
+use simulator::hexfile;
 
 fn main() {
     let args: Vec<String> = env::args().collect();
@@ -16,21 +13,7 @@ fn main() {
     let hex_path = &args[1];
     
     // 1. Load Program (Instruction Memory)
-    let path = Path::new(hex_path);
-    let file = File::open(&path).expect("Failed to open hex file");
-    let reader = BufReader::new(file);
-
-    let mut program: Vec<Instruction> = Vec::new();
-
-    for line in reader.lines() {
-        let line = line.expect("Failed read line");
-        let parts: Vec<&str> = line.split("//").collect(); 
-        let hex_str = parts[0].trim();
-        if hex_str.is_empty() { continue; }
-        
-        let word = u32::from_str_radix(hex_str, 16).expect("Invalid hex");
-        program.push(Instruction::decode(word));
-    }
+    let words = hexfile::read_hex_file(hex_path).expect("Failed to load hex file");
 
     // 2. Init Core
     let mut core = Core::new(0);
@@ -38,7 +21,7 @@ fn main() {
 
     // 3. Run
     for _ in 0..20 {
-        if let Err(e) = core.step(&program, &mut mem) {
+        if core.step_words(&words, &mut mem).is_err() {
             // Handle PC out of bounds or other errors gracefully for Oracle
              break;
         }
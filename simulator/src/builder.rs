@@ -0,0 +1,271 @@
+
+// Kernel Builder
+// Programmatic alternative to the assembler for kernels written in Rust.
+// Branch and jump targets are symbolic labels resolved in build(), so no
+// byte offsets have to be computed by hand.
+//
+//   let mut b = KernelBuilder::new();
+//   b.label("loop");
+//   b.beq(4, 3, "end");
+//   b.addi(4, 4, 1);
+//   b.j("loop");
+//   b.label("end");
+//   b.halt();
+//   let kernel = b.build()?;
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::isa::{EncodeError, Instruction, Opcode};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BuildError {
+    DuplicateLabel(String),
+    UndefinedLabel(String),
+    // Register or immediate out of range in the instruction at `index`
+    Encode { index: usize, error: EncodeError },
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BuildError::DuplicateLabel(name) => write!(f, "label '{}' defined twice", name),
+            BuildError::UndefinedLabel(name) => write!(f, "undefined label '{}'", name),
+            BuildError::Encode { index, error } => {
+                write!(f, "instruction {} (PC=0x{:x}): {}", index, index * 4, error)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+pub struct Kernel {
+    pub instructions: Vec<Instruction>,
+    pub words: Vec<u32>,
+}
+
+// An instruction whose immediate may still refer to a label
+struct Pending {
+    instr: Instruction,
+    target: Option<String>,
+}
+
+#[derive(Default)]
+pub struct KernelBuilder {
+    code: Vec<Pending>,
+    labels: HashMap<String, usize>,
+    duplicate: Option<String>,
+}
+
+impl KernelBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Byte address of the next instruction
+    pub fn pc(&self) -> usize {
+        self.code.len() * 4
+    }
+
+    pub fn label(&mut self, name: &str) -> &mut Self {
+        if self.labels.insert(name.to_string(), self.code.len()).is_some() && self.duplicate.is_none() {
+            self.duplicate = Some(name.to_string());
+        }
+        self
+    }
+
+    pub fn push(&mut self, instr: Instruction) -> &mut Self {
+        self.code.push(Pending { instr, target: None });
+        self
+    }
+
+    fn push_to_label(&mut self, instr: Instruction, label: &str) -> &mut Self {
+        self.code.push(Pending { instr, target: Some(label.to_string()) });
+        self
+    }
+
+    pub fn r_type(&mut self, opcode: Opcode, rd: usize, rs1: usize, rs2: usize) -> &mut Self {
+        self.push(Instruction::new_r_type(opcode, rd, rs1, rs2))
+    }
+
+    pub fn i_type(&mut self, opcode: Opcode, rd: usize, rs1: usize, imm: i32) -> &mut Self {
+        self.push(Instruction::new_i_type(opcode, rd, rs1, imm))
+    }
+
+    pub fn branch(&mut self, opcode: Opcode, rs1: usize, rs2: usize, label: &str) -> &mut Self {
+        self.push_to_label(Instruction::new_b_type(opcode, rs1, rs2, 0), label)
+    }
+
+    pub fn add(&mut self, rd: usize, rs1: usize, rs2: usize) -> &mut Self { self.r_type(Opcode::ADD, rd, rs1, rs2) }
+    pub fn sub(&mut self, rd: usize, rs1: usize, rs2: usize) -> &mut Self { self.r_type(Opcode::SUB, rd, rs1, rs2) }
+    pub fn sll(&mut self, rd: usize, rs1: usize, rs2: usize) -> &mut Self { self.r_type(Opcode::SLL, rd, rs1, rs2) }
+    pub fn slt(&mut self, rd: usize, rs1: usize, rs2: usize) -> &mut Self { self.r_type(Opcode::SLT, rd, rs1, rs2) }
+    pub fn sltu(&mut self, rd: usize, rs1: usize, rs2: usize) -> &mut Self { self.r_type(Opcode::SLTU, rd, rs1, rs2) }
+    pub fn xor(&mut self, rd: usize, rs1: usize, rs2: usize) -> &mut Self { self.r_type(Opcode::XOR, rd, rs1, rs2) }
+    pub fn srl(&mut self, rd: usize, rs1: usize, rs2: usize) -> &mut Self { self.r_type(Opcode::SRL, rd, rs1, rs2) }
+    pub fn sra(&mut self, rd: usize, rs1: usize, rs2: usize) -> &mut Self { self.r_type(Opcode::SRA, rd, rs1, rs2) }
+    pub fn or(&mut self, rd: usize, rs1: usize, rs2: usize) -> &mut Self { self.r_type(Opcode::OR, rd, rs1, rs2) }
+    pub fn and(&mut self, rd: usize, rs1: usize, rs2: usize) -> &mut Self { self.r_type(Opcode::AND, rd, rs1, rs2) }
+    pub fn mul(&mut self, rd: usize, rs1: usize, rs2: usize) -> &mut Self { self.r_type(Opcode::MUL, rd, rs1, rs2) }
+    pub fn mulh(&mut self, rd: usize, rs1: usize, rs2: usize) -> &mut Self { self.r_type(Opcode::MULH, rd, rs1, rs2) }
+    pub fn mulhsu(&mut self, rd: usize, rs1: usize, rs2: usize) -> &mut Self { self.r_type(Opcode::MULHSU, rd, rs1, rs2) }
+    pub fn mulhu(&mut self, rd: usize, rs1: usize, rs2: usize) -> &mut Self { self.r_type(Opcode::MULHU, rd, rs1, rs2) }
+    pub fn div(&mut self, rd: usize, rs1: usize, rs2: usize) -> &mut Self { self.r_type(Opcode::DIV, rd, rs1, rs2) }
+    pub fn divu(&mut self, rd: usize, rs1: usize, rs2: usize) -> &mut Self { self.r_type(Opcode::DIVU, rd, rs1, rs2) }
+    pub fn rem(&mut self, rd: usize, rs1: usize, rs2: usize) -> &mut Self { self.r_type(Opcode::REM, rd, rs1, rs2) }
+    pub fn remu(&mut self, rd: usize, rs1: usize, rs2: usize) -> &mut Self { self.r_type(Opcode::REMU, rd, rs1, rs2) }
+
+    pub fn addi(&mut self, rd: usize, rs1: usize, imm: i32) -> &mut Self { self.i_type(Opcode::ADDI, rd, rs1, imm) }
+    pub fn slti(&mut self, rd: usize, rs1: usize, imm: i32) -> &mut Self { self.i_type(Opcode::SLTI, rd, rs1, imm) }
+    pub fn sltiu(&mut self, rd: usize, rs1: usize, imm: i32) -> &mut Self { self.i_type(Opcode::SLTIU, rd, rs1, imm) }
+    pub fn xori(&mut self, rd: usize, rs1: usize, imm: i32) -> &mut Self { self.i_type(Opcode::XORI, rd, rs1, imm) }
+    pub fn ori(&mut self, rd: usize, rs1: usize, imm: i32) -> &mut Self { self.i_type(Opcode::ORI, rd, rs1, imm) }
+    pub fn andi(&mut self, rd: usize, rs1: usize, imm: i32) -> &mut Self { self.i_type(Opcode::ANDI, rd, rs1, imm) }
+    pub fn slli(&mut self, rd: usize, rs1: usize, shamt: i32) -> &mut Self { self.i_type(Opcode::SLLI, rd, rs1, shamt) }
+    pub fn srli(&mut self, rd: usize, rs1: usize, shamt: i32) -> &mut Self { self.i_type(Opcode::SRLI, rd, rs1, shamt) }
+    pub fn srai(&mut self, rd: usize, rs1: usize, shamt: i32) -> &mut Self { self.i_type(Opcode::SRAI, rd, rs1, shamt) }
+
+    pub fn lb(&mut self, rd: usize, rs1: usize, offset: i32) -> &mut Self { self.i_type(Opcode::LB, rd, rs1, offset) }
+    pub fn lh(&mut self, rd: usize, rs1: usize, offset: i32) -> &mut Self { self.i_type(Opcode::LH, rd, rs1, offset) }
+    pub fn lw(&mut self, rd: usize, rs1: usize, offset: i32) -> &mut Self { self.i_type(Opcode::LW, rd, rs1, offset) }
+    pub fn lbu(&mut self, rd: usize, rs1: usize, offset: i32) -> &mut Self { self.i_type(Opcode::LBU, rd, rs1, offset) }
+    pub fn lhu(&mut self, rd: usize, rs1: usize, offset: i32) -> &mut Self { self.i_type(Opcode::LHU, rd, rs1, offset) }
+    pub fn sb(&mut self, rs2: usize, rs1: usize, offset: i32) -> &mut Self {
+        self.push(Instruction::new_s_type(Opcode::SB, rs1, rs2, offset))
+    }
+    pub fn sh(&mut self, rs2: usize, rs1: usize, offset: i32) -> &mut Self {
+        self.push(Instruction::new_s_type(Opcode::SH, rs1, rs2, offset))
+    }
+    pub fn sw(&mut self, rs2: usize, rs1: usize, offset: i32) -> &mut Self {
+        // Operand order follows assembly syntax: sw rs2, offset(rs1)
+        self.push(Instruction::new_s_type(Opcode::SW, rs1, rs2, offset))
+    }
+
+    pub fn beq(&mut self, rs1: usize, rs2: usize, label: &str) -> &mut Self { self.branch(Opcode::BEQ, rs1, rs2, label) }
+    pub fn bne(&mut self, rs1: usize, rs2: usize, label: &str) -> &mut Self { self.branch(Opcode::BNE, rs1, rs2, label) }
+    pub fn blt(&mut self, rs1: usize, rs2: usize, label: &str) -> &mut Self { self.branch(Opcode::BLT, rs1, rs2, label) }
+    pub fn bge(&mut self, rs1: usize, rs2: usize, label: &str) -> &mut Self { self.branch(Opcode::BGE, rs1, rs2, label) }
+    pub fn bltu(&mut self, rs1: usize, rs2: usize, label: &str) -> &mut Self { self.branch(Opcode::BLTU, rs1, rs2, label) }
+    pub fn bgeu(&mut self, rs1: usize, rs2: usize, label: &str) -> &mut Self { self.branch(Opcode::BGEU, rs1, rs2, label) }
+
+    pub fn jal(&mut self, rd: usize, label: &str) -> &mut Self {
+        self.push_to_label(Instruction::new_j_type(Opcode::JAL, rd, 0), label)
+    }
+    pub fn jalr(&mut self, rd: usize, rs1: usize, offset: i32) -> &mut Self { self.i_type(Opcode::JALR, rd, rs1, offset) }
+    pub fn lui(&mut self, rd: usize, imm: i32) -> &mut Self { self.push(Instruction::new_u_type(Opcode::LUI, rd, imm)) }
+    pub fn auipc(&mut self, rd: usize, imm: i32) -> &mut Self { self.push(Instruction::new_u_type(Opcode::AUIPC, rd, imm)) }
+    pub fn halt(&mut self) -> &mut Self { self.i_type(Opcode::HALT, 0, 0, 0) }
+    pub fn mret(&mut self) -> &mut Self { self.i_type(Opcode::MRET, 0, 0, 0x302) }
+
+    // Zicsr: the CSR number travels in the immediate, zimm in the rs1 field
+    pub fn csrrw(&mut self, rd: usize, csr: u32, rs1: usize) -> &mut Self { self.i_type(Opcode::CSRRW, rd, rs1, csr as i32) }
+    pub fn csrrs(&mut self, rd: usize, csr: u32, rs1: usize) -> &mut Self { self.i_type(Opcode::CSRRS, rd, rs1, csr as i32) }
+    pub fn csrrc(&mut self, rd: usize, csr: u32, rs1: usize) -> &mut Self { self.i_type(Opcode::CSRRC, rd, rs1, csr as i32) }
+    pub fn csrrwi(&mut self, rd: usize, csr: u32, zimm: usize) -> &mut Self { self.i_type(Opcode::CSRRWI, rd, zimm, csr as i32) }
+    pub fn csrrsi(&mut self, rd: usize, csr: u32, zimm: usize) -> &mut Self { self.i_type(Opcode::CSRRSI, rd, zimm, csr as i32) }
+    pub fn csrrci(&mut self, rd: usize, csr: u32, zimm: usize) -> &mut Self { self.i_type(Opcode::CSRRCI, rd, zimm, csr as i32) }
+
+    // Pseudo-instructions
+    pub fn nop(&mut self) -> &mut Self { self.addi(0, 0, 0) }
+    pub fn mv(&mut self, rd: usize, rs: usize) -> &mut Self { self.addi(rd, rs, 0) }
+    pub fn j(&mut self, label: &str) -> &mut Self { self.jal(0, label) }
+    pub fn call(&mut self, label: &str) -> &mut Self { self.jal(1, label) }
+    pub fn ret(&mut self) -> &mut Self { self.jalr(0, 1, 0) }
+    pub fn beqz(&mut self, rs: usize, label: &str) -> &mut Self { self.beq(rs, 0, label) }
+    pub fn bnez(&mut self, rs: usize, label: &str) -> &mut Self { self.bne(rs, 0, label) }
+    pub fn csrr(&mut self, rd: usize, csr: u32) -> &mut Self { self.csrrs(rd, csr, 0) }
+    pub fn csrw(&mut self, csr: u32, rs: usize) -> &mut Self { self.csrrw(0, csr, rs) }
+
+    // Load a 32-bit constant: one ADDI if it fits in 12 bits, else LUI + ADDI
+    pub fn li(&mut self, rd: usize, value: i32) -> &mut Self {
+        if (-2048..=2047).contains(&value) {
+            return self.addi(rd, 0, value);
+        }
+        let lo = ((value & 0xFFF) ^ 0x800) - 0x800;
+        let hi = value.wrapping_sub(lo);
+        self.lui(rd, hi);
+        if lo != 0 {
+            self.addi(rd, rd, lo);
+        }
+        self
+    }
+
+    // Resolves labels to PC-relative offsets and validates every
+    // instruction by encoding it.
+    pub fn build(&self) -> Result<Kernel, BuildError> {
+        if let Some(name) = &self.duplicate {
+            return Err(BuildError::DuplicateLabel(name.clone()));
+        }
+
+        let mut instructions = Vec::with_capacity(self.code.len());
+        let mut words = Vec::with_capacity(self.code.len());
+        for (index, pending) in self.code.iter().enumerate() {
+            let mut instr = pending.instr;
+            if let Some(label) = &pending.target {
+                let target = *self.labels.get(label).ok_or_else(|| BuildError::UndefinedLabel(label.clone()))?;
+                instr.imm = (target as i32 - index as i32) * 4;
+            }
+            let word = instr.encode().map_err(|error| BuildError::Encode { index, error })?;
+            instructions.push(instr);
+            words.push(word);
+        }
+        Ok(Kernel { instructions, words })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_forward_and_backward_labels() {
+        let mut b = KernelBuilder::new();
+        b.li(3, 10).li(4, 0);
+        b.label("loop");
+        b.beq(4, 3, "end");
+        b.addi(4, 4, 1);
+        b.j("loop");
+        b.label("end");
+        b.halt();
+        let kernel = b.build().unwrap();
+
+        assert_eq!(kernel.instructions[2].imm, 12); // beq -> end
+        assert_eq!(kernel.instructions[4].imm, -8); // j -> loop
+        assert_eq!(kernel.words.len(), 6);
+        for (instr, word) in kernel.instructions.iter().zip(&kernel.words) {
+            assert_eq!(Instruction::decode(*word), Ok(*instr));
+        }
+    }
+
+    #[test]
+    fn test_build_errors() {
+        let mut b = KernelBuilder::new();
+        b.j("nowhere");
+        assert_eq!(b.build().err(), Some(BuildError::UndefinedLabel("nowhere".to_string())));
+
+        let mut b = KernelBuilder::new();
+        b.label("a").nop().label("a");
+        assert_eq!(b.build().err(), Some(BuildError::DuplicateLabel("a".to_string())));
+
+        let mut b = KernelBuilder::new();
+        b.nop().addi(40, 0, 1);
+        assert_eq!(
+            b.build().err(),
+            Some(BuildError::Encode { index: 1, error: EncodeError::RegisterOutOfRange { field: "rd", value: 40 } })
+        );
+
+        let mut b = KernelBuilder::new();
+        b.addi(1, 0, 5000);
+        assert!(matches!(b.build(), Err(BuildError::Encode { index: 0, .. })));
+    }
+
+    #[test]
+    fn test_li_expansion() {
+        let mut b = KernelBuilder::new();
+        b.li(1, 0x12345678).li(2, 0x1000).li(3, -1);
+        let kernel = b.build().unwrap();
+        let text: Vec<String> = kernel.instructions.iter().map(|i| i.to_string()).collect();
+        assert_eq!(text, ["lui x1, 0x12345", "addi x1, x1, 1656", "lui x2, 0x1", "addi x3, x0, -1"]);
+    }
+}
@@ -0,0 +1,210 @@
+
+// System Bus (HARDWARE_INTERFACE_v0.md section 1)
+// Routes core loads/stores by address: local SRAM at 0x0000_0000, MMIO
+// devices in their own ranges, and an access fault for anything unmapped.
+// Accesses are 1, 2 or 4 bytes; values are zero-extended to u32.
+
+use crate::error::{AccessKind, SimError};
+use crate::memory::Memory;
+
+pub trait Bus {
+    fn read(&mut self, addr: u32, size: u32) -> Result<u32, SimError>;
+    fn write(&mut self, addr: u32, size: u32, value: u32) -> Result<(), SimError>;
+    // Extra cycles for an uncached access to `addr`
+    fn latency_cycles(&self, addr: u32) -> u64;
+    // Whether the L1/L2 caches may hold `addr` (MMIO registers must not be cached)
+    fn cacheable(&self, _addr: u32) -> bool {
+        true
+    }
+}
+
+// A memory-mapped peripheral. Offsets are relative to the device's base.
+pub trait Device {
+    fn name(&self) -> &str;
+    fn read(&mut self, offset: u32, size: u32) -> Result<u32, SimError>;
+    fn write(&mut self, offset: u32, size: u32, value: u32) -> Result<(), SimError>;
+    fn latency_cycles(&self) -> u64 {
+        1
+    }
+    // Advances the device by `cycles`. Bus masters get the SRAM to access.
+    // Returns the interrupt lines the device raised.
+    fn tick(&mut self, _cycles: u64, _ram: &mut Memory) -> u32 {
+        0
+    }
+}
+
+impl Bus for Memory {
+    fn read(&mut self, addr: u32, size: u32) -> Result<u32, SimError> {
+        let addr = addr as usize;
+        match size {
+            1 => self.read_u8(addr).map(u32::from),
+            2 => self.read_u16(addr).map(u32::from),
+            _ => self.read_word(addr),
+        }
+    }
+
+    fn write(&mut self, addr: u32, size: u32, value: u32) -> Result<(), SimError> {
+        let addr = addr as usize;
+        match size {
+            1 => self.write_u8(addr, value as u8),
+            2 => self.write_u16(addr, value as u16),
+            _ => self.write_word(addr, value),
+        }
+    }
+
+    fn latency_cycles(&self, _addr: u32) -> u64 {
+        self.latency_cycles as u64
+    }
+}
+
+pub struct Region {
+    pub base: u32,
+    pub size: u32,
+    pub device: Box<dyn Device>,
+}
+
+impl Region {
+    fn contains(&self, addr: u32, size: u32) -> bool {
+        addr >= self.base && (addr - self.base) as u64 + size as u64 <= self.size as u64
+    }
+}
+
+// Local SRAM at address 0 plus any number of attached devices
+pub struct SystemBus {
+    pub ram: Memory,
+    pub regions: Vec<Region>,
+}
+
+impl SystemBus {
+    pub fn new(ram: Memory) -> Self {
+        SystemBus { ram, regions: Vec::new() }
+    }
+
+    // Maps `device` at [base, base + size). Fails if the range overlaps RAM
+    // or another device.
+    pub fn attach(&mut self, base: u32, size: u32, device: Box<dyn Device>) -> Result<(), String> {
+        let end = base as u64 + size as u64;
+        if size == 0 || end > 1 << 32 {
+            return Err(format!("invalid range 0x{:08x}+0x{:x} for '{}'", base, size, device.name()));
+        }
+        if (base as u64) < self.ram.size as u64 {
+            return Err(format!("'{}' at 0x{:08x} overlaps RAM", device.name(), base));
+        }
+        if let Some(r) = self.regions.iter().find(|r| (base as u64) < r.base as u64 + r.size as u64 && end > r.base as u64) {
+            return Err(format!("'{}' at 0x{:08x} overlaps '{}'", device.name(), base, r.device.name()));
+        }
+        self.regions.push(Region { base, size, device });
+        Ok(())
+    }
+
+    fn in_ram(&self, addr: u32) -> bool {
+        (addr as usize) < self.ram.size
+    }
+
+    fn region(&mut self, addr: u32, size: u32) -> Option<&mut Region> {
+        self.regions.iter_mut().find(|r| r.contains(addr, size))
+    }
+
+    // Advances every device, returning the interrupt lines they raised
+    pub fn tick(&mut self, cycles: u64) -> u32 {
+        let ram = &mut self.ram;
+        self.regions.iter_mut().fold(0, |irq, r| irq | r.device.tick(cycles, ram))
+    }
+
+    // The device mapped at `base`, if any
+    pub fn device_at(&mut self, base: u32) -> Option<&mut dyn Device> {
+        self.regions.iter_mut().find(|r| r.base == base).map(|r| r.device.as_mut() as &mut dyn Device)
+    }
+}
+
+impl Bus for SystemBus {
+    fn read(&mut self, addr: u32, size: u32) -> Result<u32, SimError> {
+        if self.in_ram(addr) {
+            return self.ram.read(addr, size);
+        }
+        match self.region(addr, size) {
+            Some(r) => r.device.read(addr - r.base, size),
+            None => Err(SimError::AccessFault { addr, kind: AccessKind::Read }),
+        }
+    }
+
+    fn write(&mut self, addr: u32, size: u32, value: u32) -> Result<(), SimError> {
+        if self.in_ram(addr) {
+            return self.ram.write(addr, size, value);
+        }
+        match self.region(addr, size) {
+            Some(r) => r.device.write(addr - r.base, size, value),
+            None => Err(SimError::AccessFault { addr, kind: AccessKind::Write }),
+        }
+    }
+
+    fn latency_cycles(&self, addr: u32) -> u64 {
+        if self.in_ram(addr) {
+            return self.ram.latency_cycles as u64;
+        }
+        self.regions
+            .iter()
+            .find(|r| r.contains(addr, 1))
+            .map_or(0, |r| r.device.latency_cycles())
+    }
+
+    fn cacheable(&self, addr: u32) -> bool {
+        self.in_ram(addr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Four word registers; reading register 3 returns how often it was read
+    struct Scratch {
+        regs: [u32; 4],
+    }
+
+    impl Device for Scratch {
+        fn name(&self) -> &str {
+            "scratch"
+        }
+        fn read(&mut self, offset: u32, _size: u32) -> Result<u32, SimError> {
+            if offset == 12 {
+                self.regs[3] += 1;
+            }
+            Ok(self.regs[offset as usize / 4])
+        }
+        fn write(&mut self, offset: u32, _size: u32, value: u32) -> Result<(), SimError> {
+            self.regs[offset as usize / 4] = value;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_routing_and_unmapped_faults() {
+        let mut bus = SystemBus::new(Memory::new(64, 3));
+        bus.attach(0x4000_0000, 16, Box::new(Scratch { regs: [0; 4] })).unwrap();
+
+        bus.write(0x10, 2, 0xBEEF).unwrap();
+        assert_eq!(bus.read(0x10, 4).unwrap(), 0xBEEF);
+        bus.write(0x4000_0004, 4, 7).unwrap();
+        assert_eq!(bus.read(0x4000_0004, 4).unwrap(), 7);
+        assert_eq!(bus.read(0x4000_000C, 4).unwrap(), 1);
+        assert_eq!(bus.read(0x4000_000C, 4).unwrap(), 2);
+
+        assert_eq!(bus.read(0x40, 4), Err(SimError::AccessFault { addr: 0x40, kind: AccessKind::Read }));
+        assert_eq!(
+            bus.write(0x4000_0010, 4, 0),
+            Err(SimError::AccessFault { addr: 0x4000_0010, kind: AccessKind::Write })
+        );
+        assert_eq!((bus.latency_cycles(0), bus.latency_cycles(0x4000_0000)), (3, 1));
+        assert!(bus.cacheable(0x20) && !bus.cacheable(0x4000_0000));
+    }
+
+    #[test]
+    fn test_attach_rejects_overlaps() {
+        let mut bus = SystemBus::new(Memory::new(64, 0));
+        assert!(bus.attach(0x20, 16, Box::new(Scratch { regs: [0; 4] })).is_err());
+        bus.attach(0x1000, 16, Box::new(Scratch { regs: [0; 4] })).unwrap();
+        assert!(bus.attach(0x1008, 16, Box::new(Scratch { regs: [0; 4] })).is_err());
+        assert!(bus.attach(0x0FF8, 8, Box::new(Scratch { regs: [0; 4] })).is_ok());
+    }
+}
@@ -0,0 +1,336 @@
+
+// Cache Model (HARDWARE_INTERFACE_future.md section 3)
+// Tag-only caches layered over Memory: data always lives in Memory, the
+// caches only track which lines are resident to decide the access latency.
+// Every transfer to the next level (line fill, write-back, write-through or a
+// write miss without allocation) is charged by a NextLevel: a fixed
+// miss_latency by default, or a shared L2 and DRAM via BackingStore. There
+// is no write buffer, so writes to the next level are never hidden.
+
+use crate::dram::{Dram, DramConfig};
+
+// Whatever serves a cache's misses
+pub trait NextLevel {
+    // Cycles to read or write the line containing `addr`
+    fn transfer(&mut self, addr: u32, write: bool) -> u64;
+}
+
+// A flat latency per transfer
+pub struct FixedLatency(pub u64);
+
+impl NextLevel for FixedLatency {
+    fn transfer(&mut self, _addr: u32, _write: bool) -> u64 {
+        self.0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Replacement {
+    Lru,
+    Fifo,
+    Random,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WritePolicy {
+    WriteBack,
+    WriteThrough,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CacheConfig {
+    pub size: usize,      // Total capacity in bytes
+    pub line_size: usize, // Bytes per line
+    pub ways: usize,      // 1 = direct mapped
+    pub replacement: Replacement,
+    pub write_policy: WritePolicy,
+    pub write_allocate: bool,
+    pub hit_latency: u64,  // Extra cycles for any access
+    pub miss_latency: u64, // Per line transfer when there is no BackingStore
+}
+
+impl CacheConfig {
+    // A unified 256KB 8-way L2
+    pub fn l2() -> Self {
+        CacheConfig { size: 256 * 1024, line_size: 64, ways: 8, hit_latency: 8, ..CacheConfig::default() }
+    }
+}
+
+impl Default for CacheConfig {
+    // The planned 16KB direct-mapped L1
+    fn default() -> Self {
+        CacheConfig {
+            size: 16 * 1024,
+            line_size: 32,
+            ways: 1,
+            replacement: Replacement::Lru,
+            write_policy: WritePolicy::WriteBack,
+            write_allocate: true,
+            hit_latency: 1,
+            miss_latency: 10,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CacheStats {
+    pub reads: u64,
+    pub writes: u64,
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,  // Valid lines replaced
+    pub writebacks: u64, // Dirty lines written back to memory
+}
+
+impl CacheStats {
+    pub fn hit_rate(&self) -> f64 {
+        let accesses = self.hits + self.misses;
+        if accesses == 0 { 0.0 } else { self.hits as f64 / accesses as f64 }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Line {
+    valid: bool,
+    dirty: bool,
+    tag: u32,
+    last_used: u64, // LRU
+    filled: u64,    // FIFO
+}
+
+#[derive(Debug, Clone)]
+pub struct Cache {
+    pub config: CacheConfig,
+    pub stats: CacheStats,
+    sets: Vec<Vec<Line>>,
+    clock: u64,
+    rng: u32, // xorshift state for Random replacement
+}
+
+impl Cache {
+    pub fn new(config: CacheConfig) -> Result<Self, String> {
+        if !config.line_size.is_power_of_two() {
+            return Err(format!("line size must be a power of two, got {}", config.line_size));
+        }
+        let set_bytes = config.line_size.checked_mul(config.ways).filter(|&b| b > 0);
+        let Some(set_bytes) = set_bytes.filter(|&b| config.size.is_multiple_of(b)) else {
+            return Err(format!("cache size {} must be a multiple of line_size * ways", config.size));
+        };
+        let num_sets = config.size / set_bytes;
+        if !num_sets.is_power_of_two() {
+            return Err(format!("number of sets must be a power of two, got {}", num_sets));
+        }
+        Ok(Cache {
+            config,
+            stats: CacheStats::default(),
+            sets: vec![vec![Line::default(); config.ways]; num_sets],
+            clock: 0,
+            rng: 0x2545_F491,
+        })
+    }
+
+    // Invalidate every line and clear the statistics
+    pub fn reset(&mut self) {
+        for set in &mut self.sets {
+            set.fill(Line::default());
+        }
+        self.stats = CacheStats::default();
+        self.clock = 0;
+        self.rng = 0x2545_F491;
+    }
+
+    fn locate(&self, addr: u32) -> (usize, u32) {
+        let line = addr as usize / self.config.line_size;
+        (line % self.sets.len(), (line / self.sets.len()) as u32)
+    }
+
+    fn victim(&mut self, set: usize) -> usize {
+        let lines = &self.sets[set];
+        if let Some(way) = lines.iter().position(|l| !l.valid) {
+            return way;
+        }
+        match self.config.replacement {
+            Replacement::Lru => (0..lines.len()).min_by_key(|&w| lines[w].last_used).unwrap(),
+            Replacement::Fifo => (0..lines.len()).min_by_key(|&w| lines[w].filled).unwrap(),
+            Replacement::Random => {
+                self.rng ^= self.rng << 13;
+                self.rng ^= self.rng >> 17;
+                self.rng ^= self.rng << 5;
+                self.rng as usize % lines.len()
+            }
+        }
+    }
+
+    // Looks up `addr`, updates the cache state and returns the extra cycles
+    // the access costs, with a fixed miss_latency per line transfer
+    pub fn access(&mut self, addr: u32, write: bool) -> u64 {
+        let mut next = FixedLatency(self.config.miss_latency);
+        self.access_via(addr, write, &mut next)
+    }
+
+    // Same as `access`, with line transfers served by `next`
+    pub fn access_via(&mut self, addr: u32, write: bool, next: &mut dyn NextLevel) -> u64 {
+        let config = self.config;
+        self.clock += 1;
+        if write { self.stats.writes += 1 } else { self.stats.reads += 1 }
+
+        let (set, tag) = self.locate(addr);
+        let write_through = write && config.write_policy == WritePolicy::WriteThrough;
+        let mut cycles = config.hit_latency;
+
+        if let Some(line) = self.sets[set].iter_mut().find(|l| l.valid && l.tag == tag) {
+            self.stats.hits += 1;
+            line.last_used = self.clock;
+            if write_through {
+                cycles += next.transfer(addr, true);
+            } else if write {
+                line.dirty = true;
+            }
+            return cycles;
+        }
+
+        self.stats.misses += 1;
+        if write && !config.write_allocate {
+            return cycles + next.transfer(addr, true); // Straight to the next level
+        }
+
+        let way = self.victim(set);
+        let old = self.sets[set][way];
+        if old.valid {
+            self.stats.evictions += 1;
+            if old.dirty {
+                self.stats.writebacks += 1;
+                let victim_line = old.tag as usize * self.sets.len() + set;
+                cycles += next.transfer((victim_line * config.line_size) as u32, true);
+            }
+        }
+        cycles += next.transfer(addr & !(config.line_size as u32 - 1), false); // Line fill
+        if write_through {
+            cycles += next.transfer(addr, true);
+        }
+        self.sets[set][way] = Line {
+            valid: true,
+            dirty: write && !write_through,
+            tag,
+            last_used: self.clock,
+            filled: self.clock,
+        };
+        cycles
+    }
+}
+
+// Shared levels behind the L1s: an optional unified L2 in front of DRAM
+#[derive(Debug, Clone)]
+pub struct BackingStore {
+    pub l2: Option<Cache>,
+    pub dram: Dram,
+}
+
+impl Default for BackingStore {
+    fn default() -> Self {
+        BackingStore::new(Some(CacheConfig::l2()), DramConfig::default()).unwrap()
+    }
+}
+
+impl BackingStore {
+    pub fn new(l2: Option<CacheConfig>, dram: DramConfig) -> Result<Self, String> {
+        Ok(BackingStore { l2: l2.map(Cache::new).transpose()?, dram: Dram::new(dram)? })
+    }
+
+    pub fn reset(&mut self) {
+        if let Some(l2) = self.l2.as_mut() {
+            l2.reset();
+        }
+        self.dram.reset();
+    }
+}
+
+impl NextLevel for BackingStore {
+    fn transfer(&mut self, addr: u32, write: bool) -> u64 {
+        match self.l2.as_mut() {
+            Some(l2) => l2.access_via(addr, write, &mut self.dram),
+            None => self.dram.transfer(addr, write),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn small(ways: usize, replacement: Replacement) -> Cache {
+        // 4 lines of 16 bytes
+        Cache::new(CacheConfig { size: 64, line_size: 16, ways, replacement, ..CacheConfig::default() }).unwrap()
+    }
+
+    #[test]
+    fn test_direct_mapped_hits_and_conflicts() {
+        let mut cache = small(1, Replacement::Lru);
+        assert_eq!(cache.access(0x00, false), 1 + 10);
+        assert_eq!(cache.access(0x0C, false), 1); // Same line
+        assert_eq!(cache.access(0x40, false), 1 + 10); // Same set, evicts 0x00
+        assert_eq!(cache.access(0x00, false), 1 + 10);
+        assert_eq!(cache.stats, CacheStats { reads: 4, writes: 0, hits: 1, misses: 3, evictions: 2, writebacks: 0 });
+    }
+
+    #[test]
+    fn test_replacement_policies() {
+        // Two-way, two sets: 0x00, 0x20, 0x40 all map to set 0
+        let run = |replacement| {
+            let mut cache = small(2, replacement);
+            for addr in [0x00, 0x20, 0x00, 0x40, 0x00] {
+                cache.access(addr, false);
+            }
+            cache.stats.hits
+        };
+        assert_eq!(run(Replacement::Lru), 2); // 0x40 evicts 0x20
+        assert_eq!(run(Replacement::Fifo), 1); // 0x40 evicts 0x00
+    }
+
+    #[test]
+    fn test_write_policies() {
+        let mut wb = small(1, Replacement::Lru);
+        assert_eq!(wb.access(0x00, true), 1 + 10); // Allocate
+        assert_eq!(wb.access(0x00, true), 1);
+        assert_eq!(wb.access(0x40, false), 1 + 10 + 10); // Dirty victim written back
+        assert_eq!(wb.stats.writebacks, 1);
+
+        let mut wt = Cache::new(CacheConfig {
+            size: 64,
+            line_size: 16,
+            write_policy: WritePolicy::WriteThrough,
+            write_allocate: false,
+            ..CacheConfig::default()
+        })
+        .unwrap();
+        assert_eq!(wt.access(0x00, true), 1 + 10); // No allocation
+        assert_eq!(wt.access(0x00, false), 1 + 10);
+        assert_eq!(wt.access(0x00, true), 1 + 10); // Hit, but written through
+        assert_eq!(wt.access(0x40, false), 1 + 10);
+        assert_eq!(wt.stats.writebacks, 0);
+        assert_eq!(wt.stats.hits, 1);
+    }
+
+    #[test]
+    fn test_invalid_configs_are_rejected() {
+        let config = |size, line_size, ways| CacheConfig { size, line_size, ways, ..CacheConfig::default() };
+        assert_eq!(Cache::new(config(64, 12, 1)).unwrap_err(), "line size must be a power of two, got 12");
+        assert!(Cache::new(config(64, 16, 0)).is_err());
+        assert!(Cache::new(config(100, 16, 1)).is_err());
+        assert_eq!(Cache::new(config(48, 16, 1)).unwrap_err(), "number of sets must be a power of two, got 3");
+        assert!(Cache::new(config(64, 16, usize::MAX)).is_err());
+    }
+
+    #[test]
+    fn test_l2_filters_dram_traffic() {
+        let mut backing = BackingStore::default();
+        let mut l1 = small(1, Replacement::Lru);
+        l1.access_via(0x00, false, &mut backing);
+        l1.access_via(0x40, false, &mut backing); // Evicts 0x00 from L1 only
+        let cycles = l1.access_via(0x00, false, &mut backing);
+        assert_eq!(cycles, 1 + 8); // L1 miss, L2 hit
+        let l2 = backing.l2.as_ref().unwrap().stats;
+        assert_eq!((l2.hits, l2.misses), (1, 2));
+        assert_eq!(backing.dram.stats.reads, 2);
+    }
+}
@@ -1,17 +1,79 @@
-
 // Core Simulation Unit
 
+use crate::cache::{BackingStore, Cache, CacheConfig, NextLevel};
+use crate::dram::DramConfig;
+use crate::csr::{self, CsrFile, MSTATUS_MIE, MSTATUS_MPIE};
+use crate::error::{AccessKind, SimError};
 use crate::isa::{Instruction, Opcode};
-use crate::memory::Memory;
+use crate::bus::Bus;
+use crate::pipeline::Pipeline;
+use crate::trap::Trap;
 
 pub const REG_COUNT: usize = 32;
 
+// Extra cycles charged on top of the base cycle for each M-extension op
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MulDivLatency {
+    pub mul: u64,
+    pub mulh: u64,
+    pub mulhsu: u64,
+    pub mulhu: u64,
+    pub div: u64,
+    pub divu: u64,
+    pub rem: u64,
+    pub remu: u64,
+}
+
+impl Default for MulDivLatency {
+    fn default() -> Self {
+        MulDivLatency { mul: 2, mulh: 2, mulhsu: 2, mulhu: 2, div: 10, divu: 10, rem: 10, remu: 10 }
+    }
+}
+
+impl MulDivLatency {
+    pub fn extra_cycles(&self, opcode: Opcode) -> u64 {
+        match opcode {
+            Opcode::MUL => self.mul,
+            Opcode::MULH => self.mulh,
+            Opcode::MULHSU => self.mulhsu,
+            Opcode::MULHU => self.mulhu,
+            Opcode::DIV => self.div,
+            Opcode::DIVU => self.divu,
+            Opcode::REM => self.rem,
+            Opcode::REMU => self.remu,
+            _ => 0,
+        }
+    }
+}
+
 pub struct Core {
     pub id: usize,
     pub pc: usize, // Program Counter
     pub regs: [i32; REG_COUNT],
-    pub cycle_count: u64,
+    pub cycle_count: u64, // mcycle
+    pub elapsed_cycles: u64, // Cycles since reset; unlike mcycle, software cannot write it
     pub halted: bool,
+    pub muldiv_latency: MulDivLatency,
+    // Raise SimError::DivideByZero instead of the RV32M-defined result
+    pub trap_on_divide_by_zero: bool,
+    // Accept loads/stores that are not naturally aligned instead of raising
+    // SimError::MisalignedAccess, charging `misaligned_penalty` extra cycles
+    pub allow_misaligned: bool,
+    pub misaligned_penalty: u64,
+    // Cycle-approximate 5-stage timing; None charges a flat cycle per instruction
+    pub pipeline: Option<Pipeline>,
+    // L1 caches; without them fetch is free and data accesses cost the flat
+    // Bus::latency_cycles (or go straight to the backing store)
+    pub icache: Option<Cache>,
+    pub dcache: Option<Cache>,
+    // Shared L2 + DRAM behind the L1s; None charges each L1 miss a fixed latency
+    pub backing: Option<BackingStore>,
+    pub trap: Option<Trap>, // Set when the core halted on a fault
+    pub instret: u64,       // Retired instructions (minstret)
+    pub csrs: CsrFile,
+    // Value written to mcycle/mcycleh by the executing instruction; it takes
+    // effect once that instruction has been timed
+    mcycle_write: Option<u64>,
 }
 
 impl Core {
@@ -21,10 +83,59 @@ impl Core {
             pc: 0,
             regs: [0; REG_COUNT],
             cycle_count: 0,
+            elapsed_cycles: 0,
             halted: false,
+            muldiv_latency: MulDivLatency::default(),
+            trap_on_divide_by_zero: false,
+            allow_misaligned: false,
+            misaligned_penalty: 1,
+            pipeline: None,
+            icache: None,
+            dcache: None,
+            backing: None,
+            trap: None,
+            instret: 0,
+            csrs: CsrFile::default(),
+            mcycle_write: None,
         }
     }
 
+    // Return to the post-reset state, keeping configuration
+    pub fn reset(&mut self) {
+        self.pc = 0;
+        self.regs = [0; REG_COUNT];
+        self.cycle_count = 0;
+        self.elapsed_cycles = 0;
+        self.halted = false;
+        self.trap = None;
+        self.instret = 0;
+        self.csrs = CsrFile::default();
+        self.mcycle_write = None;
+        if let Some(pipeline) = self.pipeline.as_mut() {
+            pipeline.reset();
+        }
+        for cache in [self.icache.as_mut(), self.dcache.as_mut()].into_iter().flatten() {
+            cache.reset();
+        }
+        if let Some(backing) = self.backing.as_mut() {
+            backing.reset();
+        }
+    }
+
+    // L1 I/D caches over a shared L2 and DRAM, all with default parameters
+    pub fn with_memory_hierarchy(mut self) -> Result<Self, String> {
+        self.icache = Some(Cache::new(CacheConfig::default())?);
+        self.dcache = Some(Cache::new(CacheConfig::default())?);
+        self.backing = Some(BackingStore::new(Some(CacheConfig::l2()), DramConfig::default())?);
+        Ok(self)
+    }
+
+    // Switch to the pipeline timing model
+    pub fn with_pipeline(mut self) -> Self {
+        self.pipeline = Some(Pipeline::new());
+        self
+    }
+
     // Helper to get register value (x0 is always 0)
     fn get_reg(&self, idx: usize) -> i32 {
         if idx == 0 { 0 } else { self.regs[idx] }
@@ -38,7 +149,7 @@ impl Core {
 
     // Execute a single step (fetch (simulated), decode (simulated), execute)
     // In this simple simulator, 'program' is passed directly as a slice of Instructions
-    pub fn step(&mut self, program: &[Instruction], memory: &mut Memory) -> Result<(), String> {
+    pub fn step<B: Bus + ?Sized>(&mut self, program: &[Instruction], bus: &mut B) -> Result<(), SimError> {
         if self.halted {
             return Ok(());
         }
@@ -46,86 +157,720 @@ impl Core {
         // Fetch (simulated)
         // We assume program is loaded at address 0 conceptually for instruction indexing
         // PC is byte address, so index is pc / 4
-        let instruction_idx = self.pc / 4;
-        if instruction_idx >= program.len() {
-            return Err(format!("PC out of bounds: {}", self.pc));
+        let start_cycles = self.cycle_count;
+        let result = self.fetch_index(program.len())
+            .and_then(|idx| self.execute(program[idx], bus));
+        self.account_cycles(start_cycles);
+        self.take_trap(result)
+    }
+
+    // Same as `step`, but fetches raw machine words and decodes them here, so
+    // an undecodable word is reported with its encoding as an illegal instruction.
+    pub fn step_words<B: Bus + ?Sized>(&mut self, program: &[u32], bus: &mut B) -> Result<(), SimError> {
+        if self.halted {
+            return Ok(());
+        }
+
+        let pc = self.pc as u32;
+        let start_cycles = self.cycle_count;
+        let result = self.fetch_index(program.len()).and_then(|idx| {
+            let instr = Instruction::decode(program[idx])
+                .map_err(|error| SimError::IllegalInstruction { pc, error })?;
+            self.execute(instr, bus)
+        });
+        self.account_cycles(start_cycles);
+        self.take_trap(result)
+    }
+
+    // A faulting instruction has not modified any register, memory or the
+    // PC. With a handler installed in mtvec the trap is taken like hardware
+    // would and execution continues there; otherwise record the trap and halt
+    // so the core stops in a defined state.
+    fn take_trap(&mut self, result: Result<(), SimError>) -> Result<(), SimError> {
+        if let Err(e) = &result {
+            if let Some(trap) = Trap::from_error(e, self.pc as u32) {
+                if self.csrs.mtvec != 0 {
+                    self.enter_trap(trap);
+                    return Ok(());
+                }
+                self.trap = Some(trap);
+                self.halted = true;
+            }
+        }
+        result
+    }
+
+    // Adds the cycles of the last step to elapsed_cycles, then applies a
+    // pending mcycle write
+    fn account_cycles(&mut self, start_cycles: u64) {
+        self.elapsed_cycles += self.cycle_count - start_cycles;
+        if let Some(value) = self.mcycle_write.take() {
+            self.cycle_count = value;
+        }
+    }
+
+    fn enter_trap(&mut self, trap: Trap) {
+        let csrs = &mut self.csrs;
+        csrs.mepc = trap.pc;
+        csrs.mcause = trap.cause.code();
+        csrs.mtval = trap.tval;
+        let mpie = if csrs.mstatus & MSTATUS_MIE != 0 { MSTATUS_MPIE } else { 0 };
+        csrs.mstatus = (csrs.mstatus & !(MSTATUS_MIE | MSTATUS_MPIE)) | mpie;
+        self.pc = csrs.mtvec as usize;
+        if let Some(pipeline) = self.pipeline.as_mut() {
+            pipeline.drain();
+        }
+    }
+
+    // Counters and mhartid are views of core state; the rest live in CsrFile
+    fn read_csr(&self, addr: u32) -> Option<u32> {
+        match addr {
+            csr::MCYCLE | csr::CYCLE => Some(self.cycle_count as u32),
+            csr::MCYCLEH | csr::CYCLEH => Some((self.cycle_count >> 32) as u32),
+            csr::MINSTRET | csr::INSTRET => Some(self.instret as u32),
+            csr::MINSTRETH | csr::INSTRETH => Some((self.instret >> 32) as u32),
+            csr::MHARTID => Some(self.id as u32),
+            _ => self.csrs.read(addr),
+        }
+    }
+
+    fn write_csr(&mut self, addr: u32, value: u32) -> bool {
+        if csr::is_read_only(addr) {
+            return false;
         }
-        let instr = program[instruction_idx];
+        match addr {
+            csr::MCYCLE => self.mcycle_write = Some((self.cycle_count & !0xFFFF_FFFF) | value as u64),
+            csr::MCYCLEH => self.mcycle_write = Some((self.cycle_count & 0xFFFF_FFFF) | ((value as u64) << 32)),
+            csr::MINSTRET => self.instret = (self.instret & !0xFFFF_FFFF) | value as u64,
+            csr::MINSTRETH => self.instret = (self.instret & 0xFFFF_FFFF) | ((value as u64) << 32),
+            _ => return self.csrs.write(addr, value),
+        }
+        true
+    }
 
+    // Zicsr read-modify-write. CSRRS/CSRRC with rs1 = x0 (or zimm = 0) do not
+    // write, so they are allowed on read-only CSRs.
+    fn execute_csr(&mut self, instr: Instruction, rs1: i32) -> Result<(), SimError> {
+        let addr = instr.imm as u32;
+        let illegal = SimError::IllegalCsrAccess {
+            pc: self.pc as u32,
+            csr: addr,
+            word: instr.encode().unwrap_or(0),
+        };
+        let old = match self.read_csr(addr) {
+            Some(val) => val,
+            None => return Err(illegal),
+        };
+        let src = match instr.opcode {
+            Opcode::CSRRWI | Opcode::CSRRSI | Opcode::CSRRCI => instr.rs1 as u32, // zimm
+            _ => rs1 as u32,
+        };
+        let new = match instr.opcode {
+            Opcode::CSRRW | Opcode::CSRRWI => Some(src),
+            Opcode::CSRRS | Opcode::CSRRSI if instr.rs1 != 0 => Some(old | src),
+            Opcode::CSRRC | Opcode::CSRRCI if instr.rs1 != 0 => Some(old & !src),
+            _ => None,
+        };
+        if let Some(val) = new {
+            if !self.write_csr(addr, val) {
+                return Err(illegal);
+            }
+        }
+        self.set_reg(instr.rd, old as i32);
+        Ok(())
+    }
+
+    fn fetch_index(&self, program_len: usize) -> Result<usize, SimError> {
+        if !self.pc.is_multiple_of(4) {
+            return Err(SimError::MisalignedAccess { addr: self.pc as u32, kind: AccessKind::Fetch });
+        }
+        if self.pc / 4 >= program_len {
+            return Err(SimError::PcOutOfBounds { pc: self.pc as u32 });
+        }
+        Ok(self.pc / 4)
+    }
+
+    // Loads and stores must be naturally aligned (ISA_REFERENCE.md section 4).
+    // Returns the extra cycles to charge once the access has succeeded.
+    fn check_alignment(&self, addr: u32, size: u32, kind: AccessKind) -> Result<u64, SimError> {
+        if addr.is_multiple_of(size) {
+            return Ok(0);
+        }
+        if !self.allow_misaligned {
+            return Err(SimError::MisalignedAccess { addr, kind });
+        }
+        Ok(self.misaligned_penalty)
+    }
+
+    // MMIO registers are never cached: they cost the device's own latency
+    fn data_access_cycles<B: Bus + ?Sized>(&mut self, addr: u32, write: bool, bus: &B) -> u64 {
+        if !bus.cacheable(addr) {
+            return bus.latency_cycles(addr);
+        }
+        match (self.dcache.as_mut(), self.backing.as_mut()) {
+            (Some(dcache), Some(backing)) => dcache.access_via(addr, write, backing),
+            (Some(dcache), None) => dcache.access(addr, write),
+            (None, Some(backing)) => backing.transfer(addr, write),
+            (None, None) => bus.latency_cycles(addr),
+        }
+    }
+
+    fn execute<B: Bus + ?Sized>(&mut self, instr: Instruction, bus: &mut B) -> Result<(), SimError> {
         // Execute
+        let start_cycles = self.cycle_count;
         self.cycle_count += 1; // Base cycle cost
+        let fetch_cycles = match (self.icache.as_mut(), self.backing.as_mut()) {
+            (Some(icache), Some(backing)) => icache.access_via(self.pc as u32, false, backing),
+            (Some(icache), None) => icache.access(self.pc as u32, false),
+            (None, _) => 0, // Instruction scratchpad
+        };
+        self.cycle_count += fetch_cycles;
 
         // Branch target logic
         let mut next_pc = self.pc + 4;
 
+        let rs1 = self.get_reg(instr.rs1);
+        let rs2 = self.get_reg(instr.rs2);
+        let branch_target = (self.pc as i32).wrapping_add(instr.imm) as usize;
+
+        let is_division = matches!(instr.opcode, Opcode::DIV | Opcode::DIVU | Opcode::REM | Opcode::REMU);
+        if is_division && rs2 == 0 && self.trap_on_divide_by_zero {
+            return Err(SimError::DivideByZero { pc: self.pc as u32 });
+        }
+
         match instr.opcode {
-            Opcode::ADD => {
-                let val = self.get_reg(instr.rs1).wrapping_add(self.get_reg(instr.rs2));
-                self.set_reg(instr.rd, val);
+            Opcode::ADD => self.set_reg(instr.rd, rs1.wrapping_add(rs2)),
+            Opcode::SUB => self.set_reg(instr.rd, rs1.wrapping_sub(rs2)),
+            // Only the low 5 bits of rs2 / shamt are used for shifts on RV32
+            Opcode::SLL => self.set_reg(instr.rd, rs1.wrapping_shl(rs2 as u32 & 0x1F)),
+            Opcode::SLT => self.set_reg(instr.rd, (rs1 < rs2) as i32),
+            Opcode::SLTU => self.set_reg(instr.rd, ((rs1 as u32) < (rs2 as u32)) as i32),
+            Opcode::XOR => self.set_reg(instr.rd, rs1 ^ rs2),
+            Opcode::SRL => self.set_reg(instr.rd, ((rs1 as u32) >> (rs2 as u32 & 0x1F)) as i32),
+            Opcode::SRA => self.set_reg(instr.rd, rs1 >> (rs2 as u32 & 0x1F)),
+            Opcode::OR => self.set_reg(instr.rd, rs1 | rs2),
+            Opcode::AND => self.set_reg(instr.rd, rs1 & rs2),
+            // M-extension: division never traps. Divide-by-zero yields all ones
+            // (quotient) / the dividend (remainder), and INT_MIN / -1 overflows
+            // to INT_MIN with a remainder of 0, as the RISC-V spec defines.
+            Opcode::MUL => self.set_reg(instr.rd, rs1.wrapping_mul(rs2)),
+            Opcode::MULH => {
+                let val = ((rs1 as i64) * (rs2 as i64)) >> 32;
+                self.set_reg(instr.rd, val as i32);
             }
-            Opcode::SUB => {
-                let val = self.get_reg(instr.rs1).wrapping_sub(self.get_reg(instr.rs2));
+            Opcode::MULHSU => {
+                let val = ((rs1 as i64) * (rs2 as u32 as i64)) >> 32;
+                self.set_reg(instr.rd, val as i32);
+            }
+            Opcode::MULHU => {
+                let val = ((rs1 as u32 as u64) * (rs2 as u32 as u64)) >> 32;
+                self.set_reg(instr.rd, val as i32);
+            }
+            Opcode::DIV => {
+                let val = if rs2 == 0 { -1 } else { rs1.wrapping_div(rs2) };
                 self.set_reg(instr.rd, val);
             }
-            Opcode::MUL => {
-                let val = self.get_reg(instr.rs1).wrapping_mul(self.get_reg(instr.rs2));
+            Opcode::DIVU => {
+                let val = (rs1 as u32).checked_div(rs2 as u32).unwrap_or(u32::MAX);
+                self.set_reg(instr.rd, val as i32);
+            }
+            Opcode::REM => {
+                let val = if rs2 == 0 { rs1 } else { rs1.wrapping_rem(rs2) };
                 self.set_reg(instr.rd, val);
-                self.cycle_count += 2; // Extra cost for MUL
             }
-            Opcode::DIV => {
-                 let divisor = self.get_reg(instr.rs2);
-                 if divisor == 0 {
-                     return Err("Division by zero".to_string());
-                 }
-                 let val = self.get_reg(instr.rs1).wrapping_div(divisor);
-                 self.set_reg(instr.rd, val);
-                 self.cycle_count += 10; // Extra cost for DIV
-            }
-            Opcode::ADDI => {
-                let val = self.get_reg(instr.rs1).wrapping_add(instr.imm);
+            Opcode::REMU => {
+                let val = (rs1 as u32).checked_rem(rs2 as u32).unwrap_or(rs1 as u32);
+                self.set_reg(instr.rd, val as i32);
+            }
+            Opcode::ADDI => self.set_reg(instr.rd, rs1.wrapping_add(instr.imm)),
+            Opcode::SLTI => self.set_reg(instr.rd, (rs1 < instr.imm) as i32),
+            // The immediate is sign-extended first, then compared as unsigned
+            Opcode::SLTIU => self.set_reg(instr.rd, ((rs1 as u32) < (instr.imm as u32)) as i32),
+            Opcode::XORI => self.set_reg(instr.rd, rs1 ^ instr.imm),
+            Opcode::ORI => self.set_reg(instr.rd, rs1 | instr.imm),
+            Opcode::ANDI => self.set_reg(instr.rd, rs1 & instr.imm),
+            Opcode::SLLI => self.set_reg(instr.rd, rs1.wrapping_shl(instr.imm as u32 & 0x1F)),
+            Opcode::SRLI => self.set_reg(instr.rd, ((rs1 as u32) >> (instr.imm as u32 & 0x1F)) as i32),
+            Opcode::SRAI => self.set_reg(instr.rd, rs1 >> (instr.imm as u32 & 0x1F)),
+            Opcode::LB | Opcode::LH | Opcode::LW | Opcode::LBU | Opcode::LHU => {
+                let addr = rs1.wrapping_add(instr.imm) as u32;
+                let size = access_size(instr.opcode);
+                let penalty = self.check_alignment(addr, size, AccessKind::Read)?;
+                let raw = bus.read(addr, size)?;
+                let val = match instr.opcode {
+                    Opcode::LB => raw as u8 as i8 as i32,
+                    Opcode::LH => raw as u16 as i16 as i32,
+                    _ => raw as i32,
+                };
                 self.set_reg(instr.rd, val);
+                self.cycle_count += penalty + self.data_access_cycles(addr, false, bus);
+            }
+            Opcode::SB | Opcode::SH | Opcode::SW => {
+                let addr = rs1.wrapping_add(instr.imm) as u32;
+                let size = access_size(instr.opcode);
+                let penalty = self.check_alignment(addr, size, AccessKind::Write)?;
+                bus.write(addr, size, rs2 as u32)?;
+                self.cycle_count += penalty + self.data_access_cycles(addr, true, bus);
+            }
+            Opcode::BEQ => {
+                if rs1 == rs2 {
+                    next_pc = branch_target;
+                }
             }
-            Opcode::LW => {
-                let addr = (self.get_reg(instr.rs1).wrapping_add(instr.imm)) as usize;
-                match memory.read_word(addr) {
-                    Ok(val) => {
-                        self.set_reg(instr.rd, val as i32);
-                        self.cycle_count += memory.latency_cycles as u64;
-                    },
-                    Err(e) => return Err(e),
+            Opcode::BNE => {
+                if rs1 != rs2 {
+                    next_pc = branch_target;
                 }
             }
-            Opcode::SW => {
-                let addr = (self.get_reg(instr.rs1).wrapping_add(instr.imm)) as usize;
-                let val = self.get_reg(instr.rs2) as u32;
-                match memory.write_word(addr, val) {
-                    Ok(_) => self.cycle_count += memory.latency_cycles as u64,
-                    Err(e) => return Err(e),
+            Opcode::BLT => {
+                if rs1 < rs2 {
+                    next_pc = branch_target;
                 }
             }
-            Opcode::BEQ => {
-                if self.get_reg(instr.rs1) == self.get_reg(instr.rs2) {
-                    next_pc = (self.pc as i32).wrapping_add(instr.imm) as usize;
+            Opcode::BGE => {
+                if rs1 >= rs2 {
+                    next_pc = branch_target;
                 }
             }
-            Opcode::BNE => {
-                if self.get_reg(instr.rs1) != self.get_reg(instr.rs2) {
-                    next_pc = (self.pc as i32).wrapping_add(instr.imm) as usize;
+            Opcode::BLTU => {
+                if (rs1 as u32) < (rs2 as u32) {
+                    next_pc = branch_target;
+                }
+            }
+            Opcode::BGEU => {
+                if (rs1 as u32) >= (rs2 as u32) {
+                    next_pc = branch_target;
                 }
             }
             Opcode::JAL => {
                 self.set_reg(instr.rd, (self.pc + 4) as i32);
-                next_pc = (self.pc as i32).wrapping_add(instr.imm) as usize;
+                next_pc = branch_target;
+            }
+            Opcode::JALR => {
+                // rs1 was read before rd is written, so rd == rs1 is safe
+                self.set_reg(instr.rd, (self.pc + 4) as i32);
+                next_pc = (rs1.wrapping_add(instr.imm) & !1) as u32 as usize;
+            }
+            Opcode::LUI => self.set_reg(instr.rd, instr.imm),
+            Opcode::AUIPC => self.set_reg(instr.rd, (self.pc as i32).wrapping_add(instr.imm)),
+            Opcode::FENCE => {
+                // Single in-order hart with no caches: memory ordering is implicit
+            }
+            Opcode::ECALL => {
+                return Err(SimError::EnvironmentCall { pc: self.pc as u32 });
+            }
+            Opcode::EBREAK => {
+                return Err(SimError::Breakpoint { pc: self.pc as u32 });
+            }
+            Opcode::MRET => {
+                let mstatus = self.csrs.mstatus;
+                let mie = if mstatus & MSTATUS_MPIE != 0 { MSTATUS_MIE } else { 0 };
+                self.csrs.mstatus = (mstatus & !MSTATUS_MIE) | mie | MSTATUS_MPIE;
+                next_pc = self.csrs.mepc as usize;
+            }
+            Opcode::CSRRW | Opcode::CSRRS | Opcode::CSRRC
+            | Opcode::CSRRWI | Opcode::CSRRSI | Opcode::CSRRCI => {
+                self.execute_csr(instr, rs1)?;
             }
             Opcode::HALT => {
                 self.halted = true;
             }
-            Opcode::UNKNOWN => {
-                return Err(format!("Illegal Opcode at PC={}", self.pc));
-            }
         }
 
+        self.cycle_count += self.muldiv_latency.extra_cycles(instr.opcode);
+        if let Some(pipeline) = self.pipeline.as_mut() {
+            // Reuse the flat model's extra cycles as EX/MEM occupancy
+            let extra = self.cycle_count - start_cycles - 1 - fetch_cycles;
+            let issued = pipeline.cycles();
+            let retired = pipeline.issue(self.pc as u32, &instr, fetch_cycles, extra, next_pc as u32);
+            self.cycle_count = start_cycles + (retired - issued);
+        }
+        self.instret += 1;
         self.pc = next_pc;
         Ok(())
     }
 }
+
+// Bytes moved by a load or store
+fn access_size(opcode: Opcode) -> u32 {
+    match opcode {
+        Opcode::LB | Opcode::LBU | Opcode::SB => 1,
+        Opcode::LH | Opcode::LHU | Opcode::SH => 2,
+        _ => 4,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::Memory;
+    use crate::isa::{DecodeError, DecodeErrorKind};
+
+    fn run(words: &[u32]) -> Core {
+        let mut core = Core::new(0);
+        let mut mem = Memory::new(1024, 0);
+        while !core.halted {
+            core.step_words(words, &mut mem).unwrap();
+        }
+        core
+    }
+
+    #[test]
+    fn test_rv32i_alu_and_compare() {
+        let core = run(&[
+            0xFF600093, // addi  x1, x0, -10
+            0x00300113, // addi  x2, x0, 3
+            0x0020A1B3, // slt   x3, x1, x2   -> 1
+            0x0020B233, // sltu  x4, x1, x2   -> 0
+            0x4020D2B3, // sra   x5, x1, x2   -> -2
+            0x0020D333, // srl   x6, x1, x2   -> 0x1FFFFFFE
+            0x00209393, // slli  x7, x1, 2    -> -40
+            0x0FF0F413, // andi  x8, x1, 0xFF -> 0xF6
+            0x123454B7, // lui   x9, 0x12345
+            0x0000007B, // halt
+        ]);
+        assert_eq!(core.regs[3], 1);
+        assert_eq!(core.regs[4], 0);
+        assert_eq!(core.regs[5], -2);
+        assert_eq!(core.regs[6], 0x1FFF_FFFE);
+        assert_eq!(core.regs[7], -40);
+        assert_eq!(core.regs[8], 0xF6);
+        assert_eq!(core.regs[9], 0x1234_5000);
+    }
+
+    #[test]
+    fn test_rv32i_control_flow() {
+        let core = run(&[
+            0x00000097, // 0:  auipc x1, 0         -> 0
+            0x01408093, // 4:  addi  x1, x1, 20    -> 20
+            0xFFF00113, // 8:  addi  x2, x0, -1
+            0x00016463, // 12: bltu  x2, x0, +8    (not taken: 0xFFFFFFFF > 0)
+            0x000081E7, // 16: jalr  x3, 0(x1)     -> jump to 20, x3 = 20
+            0x00015463, // 20: bge   x2, x0, +8    (not taken: -1 < 0)
+            0x0000007B, // 24: halt
+        ]);
+        assert_eq!(core.regs[1], 20);
+        assert_eq!(core.regs[3], 20);
+        assert_eq!(core.pc, 28);
+    }
+
+    #[test]
+    fn test_illegal_instruction_reports_encoding() {
+        let mut core = Core::new(0);
+        let mut mem = Memory::new(64, 0);
+        core.step_words(&[0x00000013, 0x40001033], &mut mem).unwrap();
+        let err = core.step_words(&[0x00000013, 0x40001033], &mut mem).unwrap_err();
+        assert_eq!(err, SimError::IllegalInstruction {
+            pc: 4,
+            error: DecodeError { word: 0x40001033, kind: DecodeErrorKind::ReservedFunct7 },
+        });
+    }
+
+    #[test]
+    fn test_rv32m_edge_cases() {
+        let core = run(&[
+            0x800000B7, // lui    x1, 0x80000     -> INT_MIN
+            0xFFF00113, // addi   x2, x0, -1
+            0x0220C1B3, // div    x3, x1, x2      -> INT_MIN (overflow)
+            0x0220E233, // rem    x4, x1, x2      -> 0
+            0x0200C2B3, // div    x5, x1, x0      -> -1
+            0x0200D333, // divu   x6, x1, x0      -> 0xFFFFFFFF
+            0x0200E3B3, // rem    x7, x1, x0      -> INT_MIN
+            0x0200F433, // remu   x8, x1, x0      -> INT_MIN
+            0x022114B3, // mulh   x9, x2, x2      -> 0
+            0x02213533, // mulhu  x10, x2, x2     -> 0xFFFFFFFE
+            0x022125B3, // mulhsu x11, x2, x2     -> -1
+            0x0000007B, // halt
+        ]);
+        assert_eq!(core.regs[3], i32::MIN);
+        assert_eq!(core.regs[4], 0);
+        assert_eq!(core.regs[5], -1);
+        assert_eq!(core.regs[6], -1);
+        assert_eq!(core.regs[7], i32::MIN);
+        assert_eq!(core.regs[8], i32::MIN);
+        assert_eq!(core.regs[9], 0);
+        assert_eq!(core.regs[10], -2);
+        assert_eq!(core.regs[11], -1);
+    }
+
+    #[test]
+    fn test_muldiv_latency_is_configurable() {
+        let mut mem = Memory::new(64, 0);
+        let mut core = Core::new(0);
+        core.muldiv_latency.div = 33;
+        while !core.halted {
+            core.step_words(&[0x0220C1B3, 0x0000007B], &mut mem).unwrap();
+        }
+        assert_eq!(core.cycle_count, 2 + 33);
+    }
+
+    #[test]
+    fn test_divide_by_zero_trap_is_opt_in() {
+        let mut mem = Memory::new(64, 0);
+        let mut core = Core::new(0);
+        core.trap_on_divide_by_zero = true;
+        let err = core.step_words(&[0x0200C2B3, 0x0000007B], &mut mem).unwrap_err(); // div x5, x1, x0
+        assert_eq!(err, SimError::DivideByZero { pc: 0 });
+    }
+
+    #[test]
+    fn test_csr_counters_and_hart_id() {
+        let program = crate::asm::assemble("
+            csrr   x1, mhartid
+            csrr   x2, minstret
+            csrrwi x3, mscratch, 7
+            csrr   x4, mscratch
+            csrr   x5, misa
+            csrs   mscratch, x1
+            csrr   x6, mscratch
+            csrr   x7, cycle
+            halt
+        ").unwrap();
+        let mut mem = Memory::new(64, 0);
+        let mut core = Core::new(3);
+        while !core.halted {
+            core.step_words(&program.words(), &mut mem).unwrap();
+        }
+        assert_eq!(core.regs[1], 3);
+        assert_eq!(core.regs[2], 1);
+        assert_eq!(core.regs[3], 0);
+        assert_eq!(core.regs[4], 7);
+        assert_eq!(core.regs[5] as u32, csr::MISA_VALUE);
+        assert_eq!(core.regs[6], 7 | 3);
+        // The reading instruction's own base cycle is already counted
+        assert_eq!(core.regs[7], 8);
+        assert_eq!(core.instret, 9);
+    }
+
+    #[test]
+    fn test_trap_handler_and_mret() {
+        let program = crate::asm::assemble("
+                    la    t0, handler
+                    csrw  mtvec, t0
+                    csrrsi x0, mstatus, 8  # MIE
+                    lw    a0, 0x7f0(zero)  # load access fault
+                    ecall
+                    halt
+            handler:
+                    csrr  a1, mcause
+                    csrr  a2, mtval
+                    csrr  a3, mstatus
+                    csrr  t1, mepc
+                    addi  t1, t1, 4        # skip the faulting instruction
+                    csrw  mepc, t1
+                    addi  s0, s0, 1
+                    mret
+        ").unwrap();
+        let mut mem = Memory::new(64, 0);
+        let mut core = Core::new(0);
+        while !core.halted {
+            core.step_words(&program.words(), &mut mem).unwrap();
+        }
+        // Two traps handled (load fault, then ecall), the last one was the ecall
+        assert_eq!(core.regs[8], 2);
+        assert_eq!(core.regs[11], 11);
+        assert_eq!(core.regs[12], 0);
+        assert_eq!(core.csrs.mepc, 24); // ecall at 20, advanced by the handler
+        assert_eq!(core.trap, None);
+        // MIE was cleared on entry and restored by MRET
+        assert_eq!(core.regs[13] as u32 & (MSTATUS_MIE | MSTATUS_MPIE), MSTATUS_MPIE);
+        assert_eq!(core.csrs.mstatus & MSTATUS_MIE, MSTATUS_MIE);
+    }
+
+    #[test]
+    fn test_illegal_csr_access_traps() {
+        let mut mem = Memory::new(64, 0);
+        let mut core = Core::new(0);
+        // csrrw x0, mhartid, x1 (read-only CSR)
+        let err = core.step_words(&[0xF1409073], &mut mem).unwrap_err();
+        assert_eq!(err, SimError::IllegalCsrAccess { pc: 0, csr: csr::MHARTID, word: 0xF1409073 });
+        assert_eq!(core.trap.unwrap().tval, 0xF1409073);
+
+        // Reading a read-only CSR is fine, an unimplemented one is not
+        core.reset();
+        core.step_words(&[0xF14020F3, 0x7C0020F3], &mut mem).unwrap(); // csrr x1, mhartid
+        let err = core.step_words(&[0xF14020F3, 0x7C0020F3], &mut mem).unwrap_err();
+        assert_eq!(err, SimError::IllegalCsrAccess { pc: 4, csr: 0x7C0, word: 0x7C0020F3 });
+    }
+
+    #[test]
+    fn test_byte_and_halfword_access() {
+        let program = crate::asm::assemble("
+            li   x1, 0x100
+            li   x2, -2        # 0xFFFFFFFE
+            sw   x2, 0(x1)
+            li   x3, 0x7F
+            sb   x3, 1(x1)     # word = 0xFFFF7FFE
+            li   x3, 0x1234
+            sh   x3, 2(x1)     # word = 0x12347FFE
+            lb   x4, 0(x1)     # 0xFE -> -2
+            lbu  x5, 0(x1)     # 254
+            lh   x6, 0(x1)     # 0x7FFE
+            lhu  x7, 2(x1)     # 0x1234
+            lw   x8, 0(x1)
+            halt
+        ").unwrap();
+        let mut mem = Memory::new(512, 0);
+        let mut core = Core::new(0);
+        while !core.halted {
+            core.step_words(&program.words(), &mut mem).unwrap();
+        }
+        assert_eq!(core.regs[4], -2);
+        assert_eq!(core.regs[5], 254);
+        assert_eq!(core.regs[6], 0x7FFE);
+        assert_eq!(core.regs[7], 0x1234);
+        assert_eq!(core.regs[8], 0x1234_7FFE);
+        assert_eq!(mem.read_u16(0x102).unwrap(), 0x1234);
+    }
+
+    #[test]
+    fn test_misaligned_access_traps_unless_allowed() {
+        // lh x2, 1(x0) / sw x2, 2(x0) / halt
+        let words = [0x00101103, 0x00202123, 0x0000007B];
+        let mut mem = Memory::new(64, 0);
+        let mut core = Core::new(0);
+        let err = core.step_words(&words, &mut mem).unwrap_err();
+        assert_eq!(err, SimError::MisalignedAccess { addr: 1, kind: AccessKind::Read });
+        assert_eq!(core.trap.unwrap().cause, crate::trap::TrapCause::LoadAddressMisaligned);
+
+        core.reset();
+        core.allow_misaligned = true;
+        core.misaligned_penalty = 5;
+        while !core.halted {
+            core.step_words(&words, &mut mem).unwrap();
+        }
+        assert_eq!(core.cycle_count, 3 + 2 * 5);
+
+        // A misaligned access that then faults on the bus is not charged the penalty
+        core.reset();
+        let fault = [0x04102103]; // lw x2, 65(x0)
+        let err = core.step_words(&fault, &mut mem).unwrap_err();
+        assert_eq!(err, SimError::AccessFault { addr: 65, kind: AccessKind::Read });
+        assert_eq!(core.cycle_count, 1);
+    }
+
+    #[test]
+    fn test_pipeline_timing_mode() {
+        // addi x1, x0, 1 / addi x2, x1, 1 (RAW) / jal x0, +4 / halt
+        let words = [0x00100093, 0x00108113, 0x0040006F, 0x0000007B];
+        let mut mem = Memory::new(64, 0);
+        let mut core = Core::new(0).with_pipeline();
+        while !core.halted {
+            core.step_words(&words, &mut mem).unwrap();
+        }
+        let pipeline = core.pipeline.as_ref().unwrap();
+        assert_eq!(core.regs[2], 2);
+        assert_eq!(pipeline.stall_cycles, 3);
+        assert_eq!((pipeline.flushes, pipeline.flush_cycles), (1, 2));
+        assert_eq!(core.cycle_count, 4 + 4 + 3 + 2);
+
+        core.reset();
+        assert_eq!(core.pipeline.as_ref().unwrap().instructions, 0);
+    }
+
+    #[test]
+    fn test_mcycle_write_with_pipeline() {
+        let program = crate::asm::assemble("
+            csrrw x0, mcycle, x0
+            csrr  x1, mcycle
+            halt
+        ").unwrap();
+        let mut mem = Memory::new(64, 0);
+        let mut core = Core::new(0).with_pipeline();
+        while !core.halted {
+            core.step_words(&program.words(), &mut mem).unwrap();
+        }
+        // The written value replaces the cycles of the writing instruction;
+        // later instructions count on from it
+        assert_eq!(core.regs[1], 1);
+        assert_eq!(core.cycle_count, 2);
+    }
+
+    #[test]
+    fn test_caches_replace_flat_memory_latency() {
+        use crate::cache::{Cache, CacheConfig};
+        let program = crate::asm::assemble("
+                    li   x1, 4
+            loop:   lw   x2, 0x100(zero)
+                    addi x1, x1, -1
+                    bnez x1, loop
+                    halt
+        ").unwrap();
+        let mut mem = Memory::new(512, 10);
+        let mut core = Core::new(0);
+        core.icache = Some(Cache::new(CacheConfig { size: 64, line_size: 16, ..CacheConfig::default() }).unwrap());
+        core.dcache = Some(Cache::new(CacheConfig { size: 64, line_size: 16, ..CacheConfig::default() }).unwrap());
+        while !core.halted {
+            core.step_words(&program.words(), &mut mem).unwrap();
+        }
+        let icache = core.icache.as_ref().unwrap().stats;
+        let dcache = core.dcache.as_ref().unwrap().stats;
+        // 14 fetches over two lines; four loads of one line
+        assert_eq!((icache.reads, icache.misses), (14, 2));
+        assert_eq!((dcache.reads, dcache.misses), (4, 1));
+        // Base cycles + 1 per fetch/load + 10 per miss
+        assert_eq!(core.cycle_count, 14 + 14 + 4 + 3 * 10);
+
+        core.reset();
+        assert_eq!(core.dcache.as_ref().unwrap().stats.reads, 0);
+    }
+
+    #[test]
+    fn test_memory_hierarchy_latency_varies() {
+        let program = crate::asm::assemble("
+            lw   x1, 0x100(zero)
+            li   x2, 0x4100
+            lw   x3, 0(x2)
+            lw   x4, 0x104(zero)
+            halt
+        ").unwrap();
+        let mut mem = Memory::new(0x8000, 10);
+        let mut core = Core::new(0).with_memory_hierarchy().unwrap();
+        while !core.halted {
+            core.step_words(&program.words(), &mut mem).unwrap();
+        }
+        let dram = core.backing.as_ref().unwrap().dram.stats;
+        // Code and 0x100 share a row, 0x4100 conflicts with it
+        assert_eq!((dram.row_misses, dram.row_hits, dram.row_conflicts), (1, 1, 1));
+        // 0x4100 also evicted 0x100 from the direct-mapped L1, the L2 still has it
+        assert_eq!(core.dcache.as_ref().unwrap().stats.hits, 0);
+        assert_eq!(core.backing.as_ref().unwrap().l2.as_ref().unwrap().stats.hits, 1);
+    }
+
+    #[test]
+    fn test_mmio_through_system_bus() {
+        use crate::bus::{Device, SystemBus};
+
+        // One register that counts the writes it sees
+        struct Counter(u32);
+        impl Device for Counter {
+            fn name(&self) -> &str { "counter" }
+            fn read(&mut self, _offset: u32, _size: u32) -> Result<u32, SimError> { Ok(self.0) }
+            fn write(&mut self, _offset: u32, _size: u32, _value: u32) -> Result<(), SimError> {
+                self.0 += 1;
+                Ok(())
+            }
+        }
+
+        let program = crate::asm::assemble("
+            lui  x1, 0x40000
+            sw   x0, 0(x1)
+            sw   x0, 0(x1)
+            lw   x2, 0(x1)
+            lw   x3, 0x100(x1)
+            halt
+        ").unwrap();
+        let mut bus = SystemBus::new(Memory::new(512, 10));
+        bus.attach(0x4000_0000, 4, Box::new(Counter(0))).unwrap();
+        let mut core = Core::new(0).with_memory_hierarchy().unwrap();
+        let mut result = Ok(());
+        while !core.halted {
+            result = core.step_words(&program.words(), &mut bus);
+        }
+        assert_eq!(result, Err(SimError::AccessFault { addr: 0x4000_0100, kind: AccessKind::Read }));
+        assert_eq!(core.regs[2], 2);
+        // The unmapped load faults, and MMIO never went through the dcache
+        assert_eq!(core.trap.as_ref().unwrap().tval, 0x4000_0100);
+        assert_eq!(core.dcache.as_ref().unwrap().stats.reads + core.dcache.as_ref().unwrap().stats.writes, 0);
+    }
+}
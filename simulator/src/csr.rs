@@ -0,0 +1,110 @@
+
+// Machine-mode Control and Status Registers
+// The core only implements M-mode, so only the CSRs needed for counters,
+// identification and trap handling exist. Counters (mcycle/minstret) and
+// mhartid are views of Core state and are handled in core.rs.
+
+// CSR addresses
+pub const MSTATUS: u32 = 0x300;
+pub const MISA: u32 = 0x301;
+pub const MTVEC: u32 = 0x305;
+pub const MSCRATCH: u32 = 0x340;
+pub const MEPC: u32 = 0x341;
+pub const MCAUSE: u32 = 0x342;
+pub const MTVAL: u32 = 0x343;
+pub const MCYCLE: u32 = 0xB00;
+pub const MINSTRET: u32 = 0xB02;
+pub const MCYCLEH: u32 = 0xB80;
+pub const MINSTRETH: u32 = 0xB82;
+pub const CYCLE: u32 = 0xC00; // Read-only shadows of mcycle/minstret
+pub const INSTRET: u32 = 0xC02;
+pub const CYCLEH: u32 = 0xC80;
+pub const INSTRETH: u32 = 0xC82;
+pub const MHARTID: u32 = 0xF14;
+
+// mstatus fields
+pub const MSTATUS_MIE: u32 = 1 << 3;
+pub const MSTATUS_MPIE: u32 = 1 << 7;
+pub const MSTATUS_MPP: u32 = 3 << 11; // Hardwired to M-mode (0b11)
+
+// MXL=1 (32-bit), extensions I and M
+pub const MISA_VALUE: u32 = (1 << 30) | (1 << 8) | (1 << 12);
+
+const NAMES: [(u32, &str); 16] = [
+    (MSTATUS, "mstatus"),
+    (MISA, "misa"),
+    (MTVEC, "mtvec"),
+    (MSCRATCH, "mscratch"),
+    (MEPC, "mepc"),
+    (MCAUSE, "mcause"),
+    (MTVAL, "mtval"),
+    (MCYCLE, "mcycle"),
+    (MINSTRET, "minstret"),
+    (MCYCLEH, "mcycleh"),
+    (MINSTRETH, "minstreth"),
+    (CYCLE, "cycle"),
+    (INSTRET, "instret"),
+    (CYCLEH, "cycleh"),
+    (INSTRETH, "instreth"),
+    (MHARTID, "mhartid"),
+];
+
+pub fn csr_name(addr: u32) -> Option<&'static str> {
+    NAMES.iter().find(|&&(a, _)| a == addr).map(|&(_, name)| name)
+}
+
+pub fn csr_address(name: &str) -> Option<u32> {
+    NAMES.iter().find(|&&(_, n)| n == name).map(|&(addr, _)| addr)
+}
+
+// CSR addresses with bits [11:10] == 0b11 are read-only by convention
+pub fn is_read_only(addr: u32) -> bool {
+    (addr >> 10) & 0x3 == 0x3
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CsrFile {
+    pub mstatus: u32,
+    pub mtvec: u32, // 0 = no handler installed: traps halt the core
+    pub mscratch: u32,
+    pub mepc: u32,
+    pub mcause: u32,
+    pub mtval: u32,
+}
+
+impl Default for CsrFile {
+    fn default() -> Self {
+        CsrFile { mstatus: MSTATUS_MPP, mtvec: 0, mscratch: 0, mepc: 0, mcause: 0, mtval: 0 }
+    }
+}
+
+impl CsrFile {
+    // Storage-backed CSRs only; returns None for anything else
+    pub fn read(&self, addr: u32) -> Option<u32> {
+        match addr {
+            MSTATUS => Some(self.mstatus),
+            MISA => Some(MISA_VALUE),
+            MTVEC => Some(self.mtvec),
+            MSCRATCH => Some(self.mscratch),
+            MEPC => Some(self.mepc),
+            MCAUSE => Some(self.mcause),
+            MTVAL => Some(self.mtval),
+            _ => None,
+        }
+    }
+
+    // WARL behaviour: unsupported bits read back as their fixed values
+    pub fn write(&mut self, addr: u32, value: u32) -> bool {
+        match addr {
+            MSTATUS => self.mstatus = (value & (MSTATUS_MIE | MSTATUS_MPIE)) | MSTATUS_MPP,
+            MISA => {} // Writes ignored, extensions cannot be disabled
+            MTVEC => self.mtvec = value & !0x3, // Direct mode only
+            MSCRATCH => self.mscratch = value,
+            MEPC => self.mepc = value & !0x3,
+            MCAUSE => self.mcause = value,
+            MTVAL => self.mtval = value,
+            _ => return false,
+        }
+        true
+    }
+}
@@ -0,0 +1,199 @@
+
+// Disassembler
+// Renders decoded instructions in canonical RISC-V assembly syntax.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::csr::csr_name;
+use crate::isa::{Instruction, Opcode, ABI_NAMES};
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DisasmOptions {
+    pub abi_names: bool, // a0/sp/ra instead of x10/x2/x1
+    pub labels: bool,    // Name in-range branch targets L0, L1, ...
+}
+
+pub fn reg_name(idx: usize, abi_names: bool) -> String {
+    if abi_names && idx < ABI_NAMES.len() {
+        ABI_NAMES[idx].to_string()
+    } else {
+        format!("x{}", idx)
+    }
+}
+
+fn fence_set(bits: i32) -> String {
+    let set: String = [(8, 'i'), (4, 'o'), (2, 'r'), (1, 'w')]
+        .iter()
+        .filter(|(mask, _)| bits & mask != 0)
+        .map(|&(_, c)| c)
+        .collect();
+    if set.is_empty() { "0".to_string() } else { set }
+}
+
+// Known CSRs by name, others as their 12-bit number
+fn csr_operand(csr: i32) -> String {
+    match csr_name(csr as u32) {
+        Some(name) => name.to_string(),
+        None => format!("0x{:x}", csr),
+    }
+}
+
+// Branch/JAL destination, if `pc` is known
+pub fn branch_target(instr: &Instruction, pc: u32) -> Option<u32> {
+    match instr.opcode {
+        Opcode::BEQ | Opcode::BNE | Opcode::BLT | Opcode::BGE | Opcode::BLTU | Opcode::BGEU
+        | Opcode::JAL => Some(pc.wrapping_add(instr.imm as u32)),
+        _ => None,
+    }
+}
+
+// Formats one instruction. Without a PC, branch targets are printed as the
+// signed byte offset encoded in the instruction.
+fn format_instruction(
+    instr: &Instruction,
+    pc: Option<u32>,
+    options: &DisasmOptions,
+    labels: &BTreeMap<u32, String>,
+) -> String {
+    let r = |idx: usize| reg_name(idx, options.abi_names);
+    let name = instr.opcode.mnemonic();
+    let target = || match pc {
+        Some(pc) => {
+            let addr = pc.wrapping_add(instr.imm as u32);
+            match labels.get(&addr) {
+                Some(label) => format!("0x{:x} <{}>", addr, label),
+                None => format!("0x{:x}", addr),
+            }
+        }
+        None => instr.imm.to_string(),
+    };
+
+    match instr.opcode {
+        Opcode::ADD | Opcode::SUB | Opcode::SLL | Opcode::SLT | Opcode::SLTU | Opcode::XOR
+        | Opcode::SRL | Opcode::SRA | Opcode::OR | Opcode::AND | Opcode::MUL | Opcode::MULH
+        | Opcode::MULHSU | Opcode::MULHU | Opcode::DIV | Opcode::DIVU | Opcode::REM
+        | Opcode::REMU => format!("{} {}, {}, {}", name, r(instr.rd), r(instr.rs1), r(instr.rs2)),
+        Opcode::ADDI | Opcode::SLTI | Opcode::SLTIU | Opcode::XORI | Opcode::ORI | Opcode::ANDI
+        | Opcode::SLLI | Opcode::SRLI | Opcode::SRAI => {
+            format!("{} {}, {}, {}", name, r(instr.rd), r(instr.rs1), instr.imm)
+        }
+        Opcode::LB | Opcode::LH | Opcode::LW | Opcode::LBU | Opcode::LHU | Opcode::JALR => {
+            format!("{} {}, {}({})", name, r(instr.rd), instr.imm, r(instr.rs1))
+        }
+        Opcode::SB | Opcode::SH | Opcode::SW => format!("{} {}, {}({})", name, r(instr.rs2), instr.imm, r(instr.rs1)),
+        Opcode::BEQ | Opcode::BNE | Opcode::BLT | Opcode::BGE | Opcode::BLTU | Opcode::BGEU => {
+            format!("{} {}, {}, {}", name, r(instr.rs1), r(instr.rs2), target())
+        }
+        Opcode::JAL => format!("{} {}, {}", name, r(instr.rd), target()),
+        Opcode::LUI | Opcode::AUIPC => {
+            format!("{} {}, 0x{:x}", name, r(instr.rd), (instr.imm as u32) >> 12)
+        }
+        Opcode::FENCE => {
+            format!("{} {}, {}", name, fence_set(instr.imm >> 4), fence_set(instr.imm))
+        }
+        Opcode::CSRRW | Opcode::CSRRS | Opcode::CSRRC => {
+            format!("{} {}, {}, {}", name, r(instr.rd), csr_operand(instr.imm), r(instr.rs1))
+        }
+        Opcode::CSRRWI | Opcode::CSRRSI | Opcode::CSRRCI => {
+            format!("{} {}, {}, {}", name, r(instr.rd), csr_operand(instr.imm), instr.rs1)
+        }
+        Opcode::ECALL | Opcode::EBREAK | Opcode::MRET | Opcode::HALT => name.to_string(),
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let text = format_instruction(self, None, &DisasmOptions::default(), &BTreeMap::new());
+        f.write_str(&text)
+    }
+}
+
+pub fn disassemble(words: &[u32], base: u32) -> String {
+    disassemble_with(words, base, &DisasmOptions { abi_names: false, labels: true })
+}
+
+// One line per word: "address:  raw-word  assembly", with label lines
+// inserted before any in-range branch target when `options.labels` is set.
+pub fn disassemble_with(words: &[u32], base: u32, options: &DisasmOptions) -> String {
+    let instrs: Vec<_> = words.iter().map(|&w| Instruction::decode(w)).collect();
+    let end = base.wrapping_add(4 * words.len() as u32);
+
+    let mut labels = BTreeMap::new();
+    if options.labels {
+        for (i, instr) in instrs.iter().enumerate() {
+            let Ok(instr) = instr else { continue };
+            if let Some(addr) = branch_target(instr, base.wrapping_add(4 * i as u32)) {
+                if addr >= base && addr < end {
+                    labels.insert(addr, String::new());
+                }
+            }
+        }
+        for (n, name) in labels.values_mut().enumerate() {
+            *name = format!("L{}", n);
+        }
+    }
+
+    let mut out = String::new();
+    for (i, (instr, word)) in instrs.iter().zip(words).enumerate() {
+        let pc = base.wrapping_add(4 * i as u32);
+        if let Some(label) = labels.get(&pc) {
+            out.push_str(&format!("{}:\n", label));
+        }
+        // Undecodable words (data, or encoding bugs) are shown as .word with the reason
+        let text = match instr {
+            Ok(instr) => format_instruction(instr, Some(pc), options, &labels),
+            Err(e) => format!(".word 0x{:08x}  # {}", word, e),
+        };
+        out.push_str(&format!("{:08x}:  {:08x}  {}\n", pc, word, text));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_canonical_syntax() {
+        let cases = [
+            (0x00A00093, "addi x1, x0, 10"),
+            (0x402081B3, "sub x3, x1, x2"),
+            (0x0000A283, "lw x5, 0(x1)"),
+            (0xFE512E23, "sw x5, -4(x2)"),
+            (0xFFF0C283, "lbu x5, -1(x1)"),
+            (0x00511123, "sh x5, 2(x2)"),
+            (0x123454B7, "lui x9, 0x12345"),
+            (0x00008067, "jalr x0, 0(x1)"),
+            (0xFE5FF06F, "jal x0, -28"),
+            (0x0FF0000F, "fence iorw, iorw"),
+            (0x300022F3, "csrrs x5, mstatus, x0"),
+            (0x3402D073, "csrrwi x0, mscratch, 5"),
+            (0x7C0010F3, "csrrw x1, 0x7c0, x0"),
+            (0x30200073, "mret"),
+            (0x0000007B, "halt"),
+        ];
+        for &(word, text) in &cases {
+            assert_eq!(Instruction::decode(word).unwrap().to_string(), text);
+        }
+    }
+
+    #[test]
+    fn test_disassemble_labels_and_abi_names() {
+        // 0: addi a0, zero, 3 / 4: addi a0, a0, -1 / 8: bne a0, zero, -4 / 12: halt
+        let words = [0x00300513, 0xFFF50513, 0xFE051EE3, 0x0000007B];
+        let text = disassemble_with(&words, 0x100, &DisasmOptions { abi_names: true, labels: true });
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "00000100:  00300513  addi a0, zero, 3");
+        assert_eq!(lines[1], "L0:");
+        assert_eq!(lines[2], "00000104:  fff50513  addi a0, a0, -1");
+        assert_eq!(lines[3], "00000108:  fe051ee3  bne a0, zero, 0x104 <L0>");
+        assert_eq!(lines[4], "0000010c:  0000007b  halt");
+
+        let text = disassemble(&[0xFFFF_FFFF], 0);
+        assert_eq!(
+            text,
+            "00000000:  ffffffff  .word 0xffffffff  # illegal instruction 0xffffffff: unknown opcode 0b1111111\n"
+        );
+    }
+}
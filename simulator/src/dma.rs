@@ -0,0 +1,207 @@
+
+// DMA Controller (HARDWARE_INTERFACE_v0.md section 2)
+// Copies LEN words from host memory (PCIe host physical addresses) into
+// device SRAM, or from SRAM back to the host when DIR is set (SRC is then the
+// SRAM address and DST the host address). A transfer takes setup_cycles +
+// LEN * cycles_per_word bus cycles; the data lands when BUSY drops, together
+// with IRQ_DMA_COMPLETE if INT_EN was set. Configuration writes are ignored while
+// a transfer is running.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::bus::Device;
+use crate::error::SimError;
+use crate::irq::IRQ_DMA_COMPLETE;
+use crate::memory::Memory;
+
+pub const DMA_BASE: u32 = 0x4000_0000;
+pub const DMA_REGS_SIZE: u32 = 0x14;
+
+// Register offsets
+pub const DMA_SRC_ADDR: u32 = 0x00;
+pub const DMA_DST_ADDR: u32 = 0x04;
+pub const DMA_LENGTH: u32 = 0x08; // In words
+pub const DMA_CONTROL: u32 = 0x0C;
+pub const DMA_STATUS: u32 = 0x10;
+
+// DMA_CONTROL bits
+pub const DMA_CTRL_START: u32 = 1 << 0; // Self-clearing
+pub const DMA_CTRL_INT_EN: u32 = 1 << 1;
+pub const DMA_CTRL_DIR: u32 = 1 << 2; // 0 = host to device, 1 = device to host
+
+// DMA_STATUS bits
+pub const DMA_STATUS_BUSY: u32 = 1 << 0;
+pub const DMA_STATUS_ERROR: u32 = 1 << 1;
+
+// Host memory is shared between the host driver and the DMA engine
+pub type HostMemory = Rc<RefCell<Memory>>;
+
+pub struct DmaController {
+    pub host: HostMemory,
+    pub setup_cycles: u64,
+    pub cycles_per_word: u64,
+    pub src: u32,
+    pub dst: u32,
+    pub len: u32,
+    pub ctrl: u32,
+    pub status: u32,
+    pub busy_cycles: u64, // Total cycles spent transferring
+    remaining: u64,
+}
+
+impl DmaController {
+    pub fn new(host: HostMemory) -> Self {
+        DmaController {
+            host,
+            setup_cycles: 10,
+            cycles_per_word: 1,
+            src: 0,
+            dst: 0,
+            len: 0,
+            ctrl: 0,
+            status: 0,
+            busy_cycles: 0,
+            remaining: 0,
+        }
+    }
+
+    fn start(&mut self) {
+        self.status = DMA_STATUS_BUSY;
+        self.remaining = self.setup_cycles + self.len as u64 * self.cycles_per_word;
+    }
+
+    // Moves the data at the end of the transfer. Out-of-range source or
+    // destination copies nothing and sets ERROR (a bus error).
+    fn finish(&mut self, ram: &mut Memory) {
+        let mut host = self.host.borrow_mut();
+        let bytes = self.len as usize * 4;
+        let (src, dst) = (self.src as usize, self.dst as usize);
+        let (from, to) = if self.ctrl & DMA_CTRL_DIR != 0 {
+            (&mut ram.data, &mut host.data)
+        } else {
+            (&mut host.data, &mut ram.data)
+        };
+        self.status = if src + bytes > from.len() || dst + bytes > to.len() {
+            DMA_STATUS_ERROR
+        } else {
+            to[dst..dst + bytes].copy_from_slice(&from[src..src + bytes]);
+            0
+        };
+    }
+}
+
+impl Device for DmaController {
+    fn name(&self) -> &str {
+        "dma"
+    }
+
+    fn read(&mut self, offset: u32, _size: u32) -> Result<u32, SimError> {
+        Ok(match offset & !3 {
+            DMA_SRC_ADDR => self.src,
+            DMA_DST_ADDR => self.dst,
+            DMA_LENGTH => self.len,
+            DMA_CONTROL => self.ctrl,
+            _ => self.status,
+        })
+    }
+
+    fn write(&mut self, offset: u32, _size: u32, value: u32) -> Result<(), SimError> {
+        if self.status & DMA_STATUS_BUSY != 0 {
+            return Ok(());
+        }
+        match offset & !3 {
+            DMA_SRC_ADDR => self.src = value,
+            DMA_DST_ADDR => self.dst = value,
+            DMA_LENGTH => self.len = value,
+            DMA_CONTROL => {
+                self.ctrl = value & !DMA_CTRL_START;
+                if value & DMA_CTRL_START != 0 {
+                    self.start();
+                }
+            }
+            _ => {} // DMA_STATUS is read-only
+        }
+        Ok(())
+    }
+
+    fn tick(&mut self, cycles: u64, ram: &mut Memory) -> u32 {
+        if self.status & DMA_STATUS_BUSY == 0 {
+            return 0;
+        }
+        let spent = cycles.min(self.remaining);
+        self.busy_cycles += spent;
+        self.remaining -= spent;
+        if self.remaining > 0 {
+            return 0;
+        }
+        self.finish(ram);
+        if self.ctrl & DMA_CTRL_INT_EN != 0 { IRQ_DMA_COMPLETE } else { 0 }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::{Bus, SystemBus};
+
+    fn system() -> (SystemBus, HostMemory) {
+        let host = Rc::new(RefCell::new(Memory::new(256, 0)));
+        let mut bus = SystemBus::new(Memory::new(256, 0));
+        let mut dma = DmaController::new(host.clone());
+        dma.setup_cycles = 4;
+        dma.cycles_per_word = 2;
+        bus.attach(DMA_BASE, DMA_REGS_SIZE, Box::new(dma)).unwrap();
+        (bus, host)
+    }
+
+    fn program(bus: &mut SystemBus, src: u32, dst: u32, len: u32, ctrl: u32) {
+        bus.write(DMA_BASE + DMA_SRC_ADDR, 4, src).unwrap();
+        bus.write(DMA_BASE + DMA_DST_ADDR, 4, dst).unwrap();
+        bus.write(DMA_BASE + DMA_LENGTH, 4, len).unwrap();
+        bus.write(DMA_BASE + DMA_CONTROL, 4, ctrl).unwrap();
+    }
+
+    #[test]
+    fn test_transfer_takes_configured_cycles() {
+        let (mut bus, host) = system();
+        host.borrow_mut().data[0x10..0x18].copy_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8]);
+        program(&mut bus, 0x10, 0x40, 2, DMA_CTRL_START | DMA_CTRL_INT_EN);
+        assert_eq!(bus.read(DMA_BASE + DMA_CONTROL, 4).unwrap(), DMA_CTRL_INT_EN);
+
+        // Ignored while busy
+        bus.write(DMA_BASE + DMA_DST_ADDR, 4, 0x80).unwrap();
+        assert_eq!(bus.tick(7), 0);
+        assert_eq!(bus.read(DMA_BASE + DMA_STATUS, 4).unwrap(), DMA_STATUS_BUSY);
+        assert_eq!(bus.ram.read_word(0x40).unwrap(), 0);
+
+        // 4 + 2 * 2 cycles
+        assert_eq!(bus.tick(1), IRQ_DMA_COMPLETE);
+        assert_eq!(bus.read(DMA_BASE + DMA_STATUS, 4).unwrap(), 0);
+        assert_eq!(bus.ram.read_word(0x40).unwrap(), 0x0403_0201);
+        assert_eq!(bus.ram.read_word(0x44).unwrap(), 0x0807_0605);
+        assert_eq!(bus.tick(1), 0);
+    }
+
+    #[test]
+    fn test_device_to_host_direction() {
+        let (mut bus, host) = system();
+        bus.ram.write_word(0x20, 0xCAFE_F00D).unwrap();
+        program(&mut bus, 0x20, 0x80, 1, DMA_CTRL_START | DMA_CTRL_DIR);
+        bus.tick(6);
+        assert_eq!(host.borrow().read_word(0x80).unwrap(), 0xCAFE_F00D);
+    }
+
+    #[test]
+    fn test_out_of_range_transfer_sets_error() {
+        let (mut bus, _host) = system();
+        program(&mut bus, 0, 0xF8, 4, DMA_CTRL_START);
+        assert_eq!(bus.tick(100), 0); // INT_EN clear
+        assert_eq!(bus.read(DMA_BASE + DMA_STATUS, 4).unwrap(), DMA_STATUS_ERROR);
+
+        // The next good transfer clears ERROR
+        program(&mut bus, 0, 0, 1, DMA_CTRL_START);
+        bus.tick(100);
+        assert_eq!(bus.read(DMA_BASE + DMA_STATUS, 4).unwrap(), 0);
+    }
+}
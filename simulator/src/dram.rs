@@ -0,0 +1,156 @@
+
+// DRAM Controller Model
+// Per-bank row buffers with open- or closed-page policy. Latencies are in
+// core cycles; bank-level parallelism and refresh are not modelled, so every
+// transfer is charged as if the controller were otherwise idle.
+//
+// Address mapping: | row | bank | column |, so consecutive rows of
+// row_size bytes are interleaved across the banks.
+
+use crate::cache::NextLevel;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PagePolicy {
+    Open,   // Keep the row open after an access (row hits possible)
+    Closed, // Auto-precharge after every access
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DramConfig {
+    pub banks: usize,
+    pub row_size: usize, // Bytes per row (per bank)
+    pub page_policy: PagePolicy,
+    pub t_cas: u64,   // Column access (read/write command to data)
+    pub t_rcd: u64,   // Row activate to column command
+    pub t_rp: u64,    // Precharge (close the open row)
+    pub t_burst: u64, // Data transfer of one burst
+}
+
+impl Default for DramConfig {
+    fn default() -> Self {
+        DramConfig {
+            banks: 8,
+            row_size: 2048,
+            page_policy: PagePolicy::Open,
+            t_cas: 14,
+            t_rcd: 14,
+            t_rp: 14,
+            t_burst: 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DramStats {
+    pub reads: u64,
+    pub writes: u64,
+    pub row_hits: u64,      // Row already open
+    pub row_misses: u64,    // Bank idle, row activated
+    pub row_conflicts: u64, // Other row open: precharge + activate
+    pub busy_cycles: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Dram {
+    pub config: DramConfig,
+    pub stats: DramStats,
+    open_rows: Vec<Option<u32>>, // Per bank
+}
+
+impl Default for Dram {
+    fn default() -> Self {
+        Dram::new(DramConfig::default()).unwrap()
+    }
+}
+
+impl Dram {
+    pub fn new(config: DramConfig) -> Result<Self, String> {
+        if config.banks == 0 || config.row_size == 0 {
+            return Err(format!(
+                "DRAM needs at least one bank and a row size, got {} banks of {} bytes",
+                config.banks, config.row_size
+            ));
+        }
+        Ok(Dram { config, stats: DramStats::default(), open_rows: vec![None; config.banks] })
+    }
+
+    // Close every row and clear the statistics
+    pub fn reset(&mut self) {
+        self.stats = DramStats::default();
+        self.open_rows.fill(None);
+    }
+
+    fn locate(&self, addr: u32) -> (usize, u32) {
+        let row_index = addr as usize / self.config.row_size;
+        (row_index % self.config.banks, (row_index / self.config.banks) as u32)
+    }
+
+    // Cycles for one burst access to `addr`
+    pub fn access(&mut self, addr: u32, write: bool) -> u64 {
+        let c = self.config;
+        let (bank, row) = self.locate(addr);
+        if write { self.stats.writes += 1 } else { self.stats.reads += 1 }
+
+        let cycles = match self.open_rows[bank] {
+            Some(open) if open == row => {
+                self.stats.row_hits += 1;
+                c.t_cas
+            }
+            Some(_) => {
+                self.stats.row_conflicts += 1;
+                c.t_rp + c.t_rcd + c.t_cas
+            }
+            None => {
+                self.stats.row_misses += 1;
+                c.t_rcd + c.t_cas
+            }
+        } + c.t_burst;
+
+        self.open_rows[bank] = match c.page_policy {
+            PagePolicy::Open => Some(row),
+            PagePolicy::Closed => None,
+        };
+        self.stats.busy_cycles += cycles;
+        cycles
+    }
+}
+
+impl NextLevel for Dram {
+    fn transfer(&mut self, addr: u32, write: bool) -> u64 {
+        self.access(addr, write)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_open_page_row_buffer() {
+        let mut dram = Dram::default();
+        assert_eq!(dram.access(0x0000, false), 14 + 14 + 4); // Activate
+        assert_eq!(dram.access(0x0040, false), 14 + 4); // Same row
+        assert_eq!(dram.access(0x0800, false), 14 + 14 + 4); // Next bank, idle
+        assert_eq!(dram.access(0x4000, true), 14 + 14 + 14 + 4); // Bank 0, other row
+        assert_eq!(
+            dram.stats,
+            DramStats { reads: 3, writes: 1, row_hits: 1, row_misses: 2, row_conflicts: 1, busy_cycles: 32 + 18 + 32 + 46 }
+        );
+    }
+
+    #[test]
+    fn test_closed_page_has_uniform_latency() {
+        let mut dram = Dram::new(DramConfig { page_policy: PagePolicy::Closed, t_cas: 5, t_rcd: 7, ..DramConfig::default() })
+            .unwrap();
+        for addr in [0x0000, 0x0040, 0x4000] {
+            assert_eq!(dram.access(addr, false), 7 + 5 + 4);
+        }
+        assert_eq!((dram.stats.row_hits, dram.stats.row_conflicts), (0, 0));
+    }
+
+    #[test]
+    fn test_geometry_is_checked() {
+        assert!(Dram::new(DramConfig { banks: 0, ..DramConfig::default() }).is_err());
+        assert!(Dram::new(DramConfig { row_size: 0, ..DramConfig::default() }).is_err());
+    }
+}
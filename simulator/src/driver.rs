@@ -2,61 +2,531 @@
 // Acts as the interface between User Space (Application) and "Hardware" (Simulator Core)
 // In a real scenario, this would be a kernel module or a user-space library wrapping ioctls.
 
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use crate::bus::{Bus, SystemBus};
+use crate::cache::CacheStats;
 use crate::core::Core;
+use crate::dma::{self, DmaController, HostMemory};
+use crate::dram::DramStats;
+use crate::error::SimError;
 use crate::memory::Memory;
+use crate::predictor::BranchStats;
+use crate::queue::{Arbitration, Command, CommandQueue, Completion, Event, EventTable, Output, QueueId, Timestamps};
+use crate::ring_dma::{self, RingDma, RingDmaConfig};
+use crate::irq::{self, InterruptController, IrqMode, SoftwareInterrupt};
 use crate::isa::Instruction;
+use crate::trap::Trap;
+
+// DMA_STATUS bits (HARDWARE_INTERFACE_v0.md)
+pub use crate::dma::{DMA_STATUS_BUSY, DMA_STATUS_ERROR};
+
+// Interrupt lines
+pub use crate::irq::{IRQ_CORE_HALT, IRQ_CORE_TRAP, IRQ_DMA_COMPLETE, IRQ_SOFTWARE};
+
+// Host RAM visible to the DMA engine; copies are staged at its start
+pub const HOST_MEMORY_SIZE: usize = 65536;
+const HOST_BOUNCE_BUFFER: u32 = 0;
+
+// Element types for write_slice/read_slice
+pub trait DeviceValue: Copy {
+    const SIZE: usize;
+    fn to_le_bytes(self) -> Vec<u8>;
+    fn from_le_bytes(bytes: &[u8]) -> Self;
+}
+
+macro_rules! device_value {
+    ($($t:ty),*) => {$(
+        impl DeviceValue for $t {
+            const SIZE: usize = std::mem::size_of::<$t>();
+            fn to_le_bytes(self) -> Vec<u8> {
+                <$t>::to_le_bytes(self).to_vec()
+            }
+            fn from_le_bytes(bytes: &[u8]) -> Self {
+                <$t>::from_le_bytes(bytes.try_into().unwrap())
+            }
+        }
+    )*};
+}
+
+device_value!(u8, i8, u16, i16, u32, i32, f32);
+
+// Device cycles a kernel may run before the watchdog fires
+pub const WATCHDOG_CYCLES: u64 = 100_000;
+
+// Handle to a launched kernel, signaled once the kernel halts or faults
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fence(u64);
+
+// Kernel launch ABI (ISA_REFERENCE.md section 6)
+// The first ARG_REGS arguments are passed in a0-a7, the rest in the argument
+// block at sp, sp + 4, ... as in the RISC-V calling convention. sp starts
+// 16-byte aligned below stack_top; results are returned in a0/a1.
+pub const REG_SP: usize = 2;
+pub const REG_A0: usize = 10;
+pub const REG_A1: usize = 11;
+pub const ARG_REGS: usize = 8;
+pub const STACK_TOP: u32 = 0x1_0000; // End of device SRAM; the stack grows down
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct KernelArgs {
+    pub entry: u32, // Initial PC
+    pub args: Vec<u32>,
+    pub stack_top: u32,
+}
+
+impl KernelArgs {
+    pub fn new(args: &[u32]) -> Self {
+        KernelArgs { entry: 0, args: args.to_vec(), stack_top: STACK_TOP }
+    }
+
+    pub fn with_entry(mut self, entry: u32) -> Self {
+        self.entry = entry;
+        self
+    }
+}
+
+impl Default for KernelArgs {
+    fn default() -> Self {
+        Self::new(&[])
+    }
+}
+
+struct Job {
+    fence: Fence,
+    kernel: Vec<Instruction>,
+    start: u64, // Device cycle it was launched at
+}
 
 pub struct AcceleratorDriver {
-    pub memory: Memory,
+    pub bus: SystemBus, // Device SRAM (bus.ram) plus MMIO peripherals
+    pub host: HostMemory, // Host RAM the DMA engine copies from
     pub core: Core,
+    pub status: u32,      // DMA_STATUS register as seen by the host
+    pub irq: InterruptController, // Asserted lines and host-side handlers
+    pub device_cycles: u64, // Simulated device clock
+    pub dma_cycles: u64,    // Device cycles spent waiting on host-initiated DMA
+    job: Option<Job>,
+    next_fence: u64,
+    completed: Option<(Fence, Result<PerfStats, SimError>)>, // Latest kernel, until waited on
+    pub arbitration: Arbitration, // Between command queues
+    pub queues: Vec<CommandQueue>,
+    events: EventTable,
+    next_seq: u64,
+    next_rr: QueueId, // Round robin starts looking here
 }
 
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct PerfStats {
     pub core_cycles: u64,
+    pub instructions: u64,
+    // Only counted when the core runs with the pipeline timing model
+    pub stall_cycles: u64,
+    pub flushes: u64,
+    pub flush_cycles: u64,
+    // Only with a branch predictor installed in the pipeline
+    pub mispredictions: u64,
+    pub branches: BTreeMap<u32, BranchStats>, // Keyed by branch PC
+    // Only when the core has the corresponding L1 cache
+    pub icache: Option<CacheStats>,
+    pub dcache: Option<CacheStats>,
+    pub l2: Option<CacheStats>,
+    pub dram: Option<DramStats>,
+    pub ret: [u32; 2], // a0/a1 when the kernel stopped
+}
+
+impl PerfStats {
+    pub fn from_core(core: &Core) -> Self {
+        let mut stats = PerfStats {
+            core_cycles: core.cycle_count,
+            instructions: core.instret,
+            icache: core.icache.as_ref().map(|c| c.stats),
+            dcache: core.dcache.as_ref().map(|c| c.stats),
+            l2: core.backing.as_ref().and_then(|b| b.l2.as_ref()).map(|c| c.stats),
+            dram: core.backing.as_ref().map(|b| b.dram.stats),
+            ret: [core.regs[REG_A0] as u32, core.regs[REG_A1] as u32],
+            ..Default::default()
+        };
+        if let Some(pipeline) = &core.pipeline {
+            stats.stall_cycles = pipeline.stall_cycles;
+            stats.flushes = pipeline.flushes;
+            stats.flush_cycles = pipeline.flush_cycles;
+            if let Some(unit) = &pipeline.branch_unit {
+                stats.mispredictions = unit.mispredictions();
+                stats.branches = unit.stats.clone();
+            }
+        }
+        stats
+    }
+}
+
+impl Default for AcceleratorDriver {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl AcceleratorDriver {
     pub fn new() -> Self {
+        // Initialize hardware with 64KB memory and 10 cycle latency
+        let mut bus = SystemBus::new(Memory::new(65536, 10));
+        let host = Rc::new(RefCell::new(Memory::new(HOST_MEMORY_SIZE, 0)));
+        bus.attach(dma::DMA_BASE, dma::DMA_REGS_SIZE, Box::new(DmaController::new(host.clone())))
+            .expect("DMA registers overlap device SRAM");
+        bus.attach(irq::SWI_BASE, irq::SWI_REGS_SIZE, Box::new(SoftwareInterrupt::default()))
+            .expect("SWI doorbell overlaps another device");
         AcceleratorDriver {
-            // Initialize hardware with 64KB memory and 10 cycle latency
-            memory: Memory::new(65536, 10),
+            bus,
+            host,
             core: Core::new(0),
+            status: 0,
+            irq: InterruptController::new(IrqMode::Intx),
+            device_cycles: 0,
+            dma_cycles: 0,
+            job: None,
+            next_fence: 0,
+            completed: None,
+            arbitration: Arbitration::Fifo,
+            queues: Vec::new(),
+            events: EventTable::default(),
+            next_seq: 0,
+            next_rr: 0,
         }
     }
 
+    // Caches, L2 and DRAM timing instead of the flat memory latency
+    pub fn with_memory_hierarchy() -> Result<Self, String> {
+        let mut driver = Self::new();
+        driver.core = Core::new(0).with_memory_hierarchy()?;
+        Ok(driver)
+    }
+
+    // Also attaches the v2.0 descriptor ring engine at RING_DMA_BASE, sharing
+    // host memory with the v0 DMA controller
+    pub fn with_ring_dma(mut self, config: RingDmaConfig) -> Self {
+        let ring = RingDma::new(config, self.host.clone());
+        self.bus
+            .attach(ring_dma::RING_DMA_BASE, ring_dma::RING_DMA_REGS_SIZE, Box::new(ring))
+            .expect("ring DMA registers overlap another device");
+        self
+    }
+
     // "ioctl"-like command to load data into device memory
-    // In a real PCIe device, this would be DMA
-    pub fn copy_to_device(&mut self, data: &[u8], addr: usize) -> Result<(), String> {
-         if addr + data.len() > self.memory.size {
-             return Err("DMA out of bounds".to_string());
-         }
-         for (i, &byte) in data.iter().enumerate() {
-             self.memory.data[addr + i] = byte;
-         }
-         Ok(())
-    }
-
-    // Submit a kernel (command buffer) for execution
-    pub fn submit_kernel(&mut self, kernel: Vec<Instruction>) -> Result<PerfStats, String> {
+    // Whole words go through the DMA engine from a host bounce buffer; an
+    // unaligned destination or a trailing partial word is written through the
+    // PCIe target window a byte at a time, like hansen_pci.c does.
+    pub fn copy_to_device(&mut self, data: &[u8], addr: usize) -> Result<(), SimError> {
+        self.check_range(addr, data.len())?;
+        let words = if addr.is_multiple_of(4) { data.len() / 4 } else { 0 };
+        let bulk = words * 4;
+        if words > 0 {
+            self.host.borrow_mut().data[..bulk].copy_from_slice(&data[..bulk]);
+            self.dma_transfer(HOST_BOUNCE_BUFFER, addr as u32, words as u32, 0)?;
+        }
+        for (i, &byte) in data[bulk..].iter().enumerate() {
+            self.bus.write((addr + bulk + i) as u32, 1, byte as u32)?;
+        }
+        Ok(())
+    }
+
+    // Reads device memory back into `out`, the reverse of copy_to_device:
+    // whole words by DMA into the host bounce buffer, the rest through the
+    // target window
+    pub fn copy_from_device(&mut self, out: &mut [u8], addr: usize) -> Result<(), SimError> {
+        self.check_range(addr, out.len())?;
+        let words = if addr.is_multiple_of(4) { out.len() / 4 } else { 0 };
+        let bulk = words * 4;
+        if words > 0 {
+            self.dma_transfer(addr as u32, HOST_BOUNCE_BUFFER, words as u32, dma::DMA_CTRL_DIR)?;
+            out[..bulk].copy_from_slice(&self.host.borrow().data[..bulk]);
+        }
+        for (i, byte) in out[bulk..].iter_mut().enumerate() {
+            *byte = self.bus.read((addr + bulk + i) as u32, 1)? as u8;
+        }
+        Ok(())
+    }
+
+    // Fills `len` bytes of device memory with `value`
+    pub fn memset_device(&mut self, addr: usize, value: u8, len: usize) -> Result<(), SimError> {
+        self.check_range(addr, len)?;
+        self.copy_to_device(&vec![value; len], addr)
+    }
+
+    // Typed wrappers around copy_to_device/copy_from_device (little-endian)
+    pub fn write_slice<T: DeviceValue>(&mut self, addr: usize, values: &[T]) -> Result<(), SimError> {
+        let bytes: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+        self.copy_to_device(&bytes, addr)
+    }
+
+    pub fn read_slice<T: DeviceValue>(&mut self, addr: usize, count: usize) -> Result<Vec<T>, SimError> {
+        let len = count.checked_mul(T::SIZE).ok_or(SimError::DmaOutOfBounds { addr: addr as u32, len: usize::MAX })?;
+        self.check_range(addr, len)?;
+        let mut bytes = vec![0; len];
+        self.copy_from_device(&mut bytes, addr)?;
+        Ok(bytes.chunks_exact(T::SIZE).map(T::from_le_bytes).collect())
+    }
+
+    // A copy must fit in device SRAM and in one bounce buffer
+    pub fn check_range(&self, addr: usize, len: usize) -> Result<(), SimError> {
+        match addr.checked_add(len) {
+            Some(end) if end <= self.bus.ram.size && len <= HOST_MEMORY_SIZE => Ok(()),
+            _ => Err(SimError::DmaOutOfBounds { addr: addr as u32, len }),
+        }
+    }
+
+    // Programs the DMA registers and polls DMA_STATUS until the engine is idle
+    fn dma_transfer(&mut self, src: u32, dst: u32, words: u32, ctrl: u32) -> Result<(), SimError> {
+        let start = self.device_cycles;
+        let bus = &mut self.bus;
+        bus.write(dma::DMA_BASE + dma::DMA_SRC_ADDR, 4, src)?;
+        bus.write(dma::DMA_BASE + dma::DMA_DST_ADDR, 4, dst)?;
+        bus.write(dma::DMA_BASE + dma::DMA_LENGTH, 4, words)?;
+        bus.write(dma::DMA_BASE + dma::DMA_CONTROL, 4, ctrl | dma::DMA_CTRL_START | dma::DMA_CTRL_INT_EN)?;
+        let mut status = bus.read(dma::DMA_BASE + dma::DMA_STATUS, 4)?;
+        while status & DMA_STATUS_BUSY != 0 {
+            self.advance();
+            status = self.bus.read(dma::DMA_BASE + dma::DMA_STATUS, 4)?;
+        }
+        self.dma_cycles += self.device_cycles - start;
+        if status & DMA_STATUS_ERROR != 0 {
+            let device_addr = if ctrl & dma::DMA_CTRL_DIR != 0 { src } else { dst };
+            return Err(SimError::DmaOutOfBounds { addr: device_addr, len: words as usize * 4 });
+        }
+        Ok(())
+    }
+
+    // Submit a kernel (command buffer) and run it to completion
+    pub fn submit_kernel(&mut self, kernel: Vec<Instruction>) -> Result<PerfStats, SimError> {
+        self.submit_kernel_with(kernel, &KernelArgs::default())
+    }
+
+    pub fn submit_kernel_with(&mut self, kernel: Vec<Instruction>, args: &KernelArgs) -> Result<PerfStats, SimError> {
+        let fence = self.launch_with(kernel, args)?;
+        // Bounded by the watchdog
+        self.wait(fence, u64::MAX)
+    }
+
+    // Starts a kernel and returns immediately; it runs as the device clock
+    // advances through tick(), wait() or DMA transfers
+    pub fn launch(&mut self, kernel: Vec<Instruction>) -> Result<Fence, SimError> {
+        self.launch_with(kernel, &KernelArgs::default())
+    }
+
+    pub fn launch_with(&mut self, kernel: Vec<Instruction>, args: &KernelArgs) -> Result<Fence, SimError> {
+        if self.job.is_some() {
+            return Err(SimError::DeviceBusy);
+        }
+        let spilled = &args.args[args.args.len().min(ARG_REGS)..];
+        let frame = (spilled.len() as u32 * 4).next_multiple_of(16);
+        let sp = args
+            .stack_top
+            .checked_sub(frame)
+            .filter(|_| args.stack_top.is_multiple_of(16) && args.stack_top as usize <= self.bus.ram.size)
+            .ok_or(SimError::StackOutOfBounds { sp: args.stack_top })?;
+        for (i, &word) in spilled.iter().enumerate() {
+            self.bus.ram.write_word(sp as usize + i * 4, word)?;
+        }
+
         // Reset core state for new execution (except maybe general memory)
-        self.core.pc = 0;
-        self.core.halted = false;
-        self.core.cycle_count = 0;
-        self.core.regs = [0; 32];
+        self.core.reset();
+        self.core.pc = args.entry as usize;
+        self.core.regs[REG_SP] = sp as i32;
+        for (i, &arg) in args.args.iter().take(ARG_REGS).enumerate() {
+            self.core.regs[REG_A0 + i] = arg as i32;
+        }
+        self.status = DMA_STATUS_BUSY;
+        let fence = Fence(self.next_fence);
+        self.next_fence += 1;
+        self.job = Some(Job { fence, kernel, start: self.device_cycles });
+        Ok(fence)
+    }
 
-        // In this mock, we execute synchronously.
-        // In real driver, this would return immediately and we'd wait for interrupt.
-        
-        let max_cycles = 100_000; // Watchdog
-        while !self.core.halted && self.core.cycle_count < max_cycles {
-             self.core.step(&kernel, &mut self.memory)?;
+    // Advances the device clock by at least `cycles`
+    pub fn tick(&mut self, cycles: u64) {
+        let target = self.device_cycles + cycles;
+        while self.device_cycles < target {
+            self.advance();
+        }
+    }
+
+    // The kernel's result once its fence is signaled, None while it runs.
+    // Only the latest kernel's result is kept, until wait() returns it, so a
+    // retired fence also polls as None.
+    pub fn poll(&self, fence: Fence) -> Option<Result<PerfStats, SimError>> {
+        match &self.completed {
+            Some((done, result)) if *done == fence => Some(result.clone()),
+            _ => None,
         }
+    }
+
+    // Runs the device until the fence is signaled or `timeout` device cycles
+    // have passed, then retires the fence. Waiting on a retired fence fails
+    // with FenceRetired.
+    pub fn wait(&mut self, fence: Fence, timeout: u64) -> Result<PerfStats, SimError> {
+        let deadline = self.device_cycles.saturating_add(timeout);
+        loop {
+            if self.poll(fence).is_some() {
+                return self.completed.take().unwrap().1;
+            }
+            if self.job.as_ref().is_none_or(|job| job.fence != fence) {
+                return Err(SimError::FenceRetired);
+            }
+            if self.device_cycles >= deadline {
+                return Err(SimError::WaitTimeout { cycles: timeout });
+            }
+            self.advance();
+        }
+    }
+
+    // One core step of the running kernel, or one idle cycle. Peripherals
+    // run alongside the core.
+    fn advance(&mut self) {
+        let Some(job) = self.job.as_ref() else {
+            self.device_cycles += 1;
+            self.irq.raise(self.bus.tick(1));
+            return;
+        };
+        let before = self.core.elapsed_cycles;
+        let result = self.core.step(&job.kernel, &mut self.bus);
+        let elapsed = self.core.elapsed_cycles - before;
+        self.device_cycles += elapsed;
+        self.irq.raise(self.bus.tick(elapsed));
+
+        let runtime = self.device_cycles - job.start;
+        let outcome = if let Err(e) = result {
+            // Trap: the core is halted on the faulting PC (ISA_REFERENCE 3.5)
+            self.status = DMA_STATUS_ERROR;
+            self.irq.raise(IRQ_CORE_HALT | IRQ_CORE_TRAP);
+            Err(e)
+        } else if self.core.halted {
+            self.status = 0;
+            self.irq.raise(IRQ_CORE_HALT);
+            Ok(PerfStats::from_core(&self.core))
+        } else if runtime >= WATCHDOG_CYCLES {
+            self.status = DMA_STATUS_ERROR;
+            self.irq.raise(IRQ_CORE_TRAP);
+            Err(SimError::WatchdogTimeout { cycles: runtime })
+        } else {
+            return;
+        };
+        let fence = self.job.take().unwrap().fence;
+        self.completed = Some((fence, outcome));
+    }
+
+    pub fn create_queue(&mut self, priority: u32) -> QueueId {
+        self.queues.push(CommandQueue::new(priority));
+        self.queues.len() - 1
+    }
+
+    pub fn create_event(&mut self) -> Event {
+        self.events.create()
+    }
+
+    // Device cycle the event was signaled at, None until a queue reaches it
+    pub fn event_timestamp(&self, event: Event) -> Result<Option<u64>, SimError> {
+        self.events.timestamp(event)
+    }
+
+    // Adds a command to a queue and returns its sequence number. Nothing runs
+    // until run_queues().
+    pub fn enqueue(&mut self, queue: QueueId, command: Command) -> Result<u64, SimError> {
+        if let Command::SignalEvent(event) = command {
+            self.events.timestamp(event)?;
+        }
+        let submitted = self.device_cycles;
+        let target = self.queues.get_mut(queue).ok_or(SimError::UnknownQueue { queue })?;
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        target.push(seq, submitted, command);
+        Ok(seq)
+    }
+
+    // Executes queued commands until every queue is empty
+    pub fn run_queues(&mut self) {
+        while let Some(id) = self.next_queue() {
+            let (seq, submitted, command) = self.queues[id].pop().unwrap();
+            let started = self.device_cycles;
+            let name = command.name();
+            let result = match self.queues[id].error.clone() {
+                Some(e) => Err(e),
+                None => self.execute_command(command),
+            };
+            if let Err(e) = &result {
+                self.queues[id].error.get_or_insert_with(|| e.clone());
+            }
+            let times = Timestamps { submitted, started, completed: self.device_cycles };
+            self.queues[id].completions.push(Completion { seq, command: name, times, result });
+            self.next_rr = id + 1;
+        }
+    }
+
+    // A barrier is ready once no other queue holds older work
+    fn ready(&self, id: QueueId) -> bool {
+        match self.queues[id].head() {
+            None => false,
+            Some((seq, Command::Barrier)) => {
+                self.queues.iter().all(|q| q.head().is_none_or(|(other, _)| other >= seq))
+            }
+            Some(_) => true,
+        }
+    }
 
-        if !self.core.halted {
-            return Err("Watchdog timer expired: Kernel took too long".to_string());
+    fn next_queue(&self) -> Option<QueueId> {
+        let n = self.queues.len();
+        let head_seq = |id: QueueId| self.queues[id].head().unwrap().0;
+        let ready = (0..n).filter(|&id| self.ready(id));
+        match self.arbitration {
+            Arbitration::Fifo => ready.min_by_key(|&id| head_seq(id)),
+            Arbitration::Priority => {
+                ready.min_by_key(|&id| (std::cmp::Reverse(self.queues[id].priority), head_seq(id)))
+            }
+            Arbitration::RoundRobin => ready.min_by_key(|&id| (id + n - self.next_rr % n) % n),
         }
+    }
 
-        Ok(PerfStats { core_cycles: self.core.cycle_count })
+    fn execute_command(&mut self, command: Command) -> Result<Output, SimError> {
+        match command {
+            Command::CopyToDevice { addr, data } => {
+                self.copy_to_device(&data, addr as usize)?;
+                Ok(Output::None)
+            }
+            Command::CopyFromDevice { addr, len } => {
+                let mut data = vec![0; len];
+                self.copy_from_device(&mut data, addr as usize)?;
+                Ok(Output::Data(data))
+            }
+            Command::LaunchKernel(kernel) => {
+                let fence = self.launch(kernel)?;
+                self.wait(fence, u64::MAX).map(|stats| Output::Kernel(Box::new(stats)))
+            }
+            Command::Barrier => Ok(Output::None),
+            Command::SignalEvent(event) => {
+                self.events.signal(event, self.device_cycles)?;
+                Ok(Output::None)
+            }
+        }
+    }
+
+    // BUSY/ERROR of the core combined with the DMA engine's
+    pub fn read_status(&mut self) -> u32 {
+        let dma = self.bus.read(dma::DMA_BASE + dma::DMA_STATUS, 4).unwrap_or(0);
+        self.status | dma
+    }
+
+    // Cause of the last kernel fault, if the last kernel trapped
+    pub fn last_trap(&self) -> Option<Trap> {
+        self.core.trap
+    }
+
+    // Asserted interrupt lines, cleared by ack_irq
+    pub fn irq_pending(&self) -> u32 {
+        self.irq.lines
+    }
+
+    pub fn ack_irq(&mut self, lines: u32) {
+        self.irq.ack(lines);
     }
 
     pub fn read_register(&self, reg_idx: usize) -> i32 {
@@ -71,14 +541,16 @@ impl AcceleratorDriver {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::error::AccessKind;
     use crate::isa::Opcode;
+    use crate::trap::TrapCause;
 
     #[test]
     fn test_driver_lifecycle() {
         let mut driver = AcceleratorDriver::new();
         
         // 1. Initial State
-        assert_eq!(driver.core.halted, false);
+        assert!(!driver.core.halted);
         assert_eq!(driver.core.cycle_count, 0);
 
         // 2. Submit Kernel (ADDI x1, x0, 10; HALT)
@@ -92,8 +564,325 @@ mod tests {
         assert!(res.is_ok());
 
         // 3. Post-Execution State
-        assert_eq!(driver.core.halted, true);
+        assert!(driver.core.halted);
         assert_eq!(driver.core.regs[1], 10);
         assert!(driver.get_perf_stats() > 0);
+        assert_eq!(driver.read_status(), 0);
+        assert_eq!(driver.irq_pending(), IRQ_CORE_HALT);
+    }
+
+    #[test]
+    fn test_trap_halts_core_and_sets_error() {
+        let mut driver = AcceleratorDriver::new();
+        // x1 = 0x10000 (end of the 64KB SRAM), then load from 4(x1)
+        let kernel = vec![
+            Instruction::new_u_type(Opcode::LUI, 1, 0x10000),
+            Instruction::new_i_type(Opcode::ADDI, 2, 0, 5),
+            Instruction::new_i_type(Opcode::LW, 2, 1, 4),
+            Instruction::new_i_type(Opcode::HALT, 0, 0, 0),
+        ];
+
+        let err = driver.submit_kernel(kernel).unwrap_err();
+        assert_eq!(err, SimError::AccessFault { addr: 0x10004, kind: AccessKind::Read });
+
+        let trap = driver.last_trap().unwrap();
+        assert_eq!(trap, Trap { cause: TrapCause::LoadAccessFault, pc: 8, tval: 0x10004 });
+        // Halted on the faulting instruction, destination untouched
+        assert!(driver.core.halted);
+        assert_eq!(driver.core.pc, 8);
+        assert_eq!(driver.read_register(2), 5);
+        assert_eq!(driver.read_status(), DMA_STATUS_ERROR);
+        assert_eq!(driver.irq_pending(), IRQ_CORE_HALT | IRQ_CORE_TRAP);
+
+        // The next submission starts from a clean state
+        driver.ack_irq(IRQ_CORE_HALT | IRQ_CORE_TRAP);
+        let ok = vec![Instruction::new_i_type(Opcode::HALT, 0, 0, 0)];
+        assert!(driver.submit_kernel(ok).is_ok());
+        assert_eq!(driver.last_trap(), None);
+        assert_eq!(driver.read_status(), 0);
+    }
+
+    #[test]
+    fn test_copy_to_device_uses_dma() {
+        let mut driver = AcceleratorDriver::new();
+        let data: Vec<u8> = (1..=10).collect();
+        driver.copy_to_device(&data, 0x100).unwrap();
+        assert_eq!(&driver.bus.ram.data[0x100..0x10A], &data[..]);
+        // Two words by DMA, the last two bytes through the target window
+        assert_eq!(driver.bus.read(dma::DMA_BASE + dma::DMA_LENGTH, 4).unwrap(), 2);
+        assert_eq!(driver.irq_pending(), IRQ_DMA_COMPLETE);
+        assert_eq!(driver.read_status(), 0);
+
+        // Unaligned destinations never touch the DMA engine
+        driver.copy_to_device(&data[..4], 0x201).unwrap();
+        assert_eq!(&driver.bus.ram.data[0x201..0x205], &data[..4]);
+        assert_eq!(driver.bus.read(dma::DMA_BASE + dma::DMA_DST_ADDR, 4).unwrap(), 0x100);
+
+        assert_eq!(
+            driver.copy_to_device(&data, 0xFFFC),
+            Err(SimError::DmaOutOfBounds { addr: 0xFFFC, len: 10 })
+        );
+    }
+
+    #[test]
+    fn test_ring_dma_is_opt_in() {
+        let mut driver = AcceleratorDriver::new();
+        let status = ring_dma::RING_DMA_BASE + ring_dma::RING_STATUS;
+        assert!(driver.bus.read(status, 4).is_err());
+
+        let mut driver = AcceleratorDriver::new().with_ring_dma(RingDmaConfig::default());
+        assert_eq!(driver.bus.read(status, 4), Ok(0));
+        // Both engines still work side by side
+        driver.copy_to_device(&[1, 2, 3, 4], 0).unwrap();
+        assert_eq!(driver.bus.ram.read_word(0).unwrap(), 0x0403_0201);
+    }
+
+    #[test]
+    fn test_msix_interrupt_driven_flow() {
+        use crate::irq::MSIX_VECTOR_SOFTWARE;
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let mut driver = AcceleratorDriver::new();
+        driver.irq.mode = IrqMode::MsiX;
+        let log = Rc::new(RefCell::new(Vec::new()));
+        for vector in 0..irq::MSIX_VECTORS {
+            let log = log.clone();
+            driver.irq.on_vector(vector, Box::new(move |v| log.borrow_mut().push(v)));
+        }
+
+        driver.copy_to_device(&[0; 8], 0).unwrap();
+        // The kernel rings the doorbell with 7, then halts
+        let kernel = vec![
+            Instruction::new_u_type(Opcode::LUI, 1, 0x4000_1000),
+            Instruction::new_i_type(Opcode::ADDI, 2, 0, 7),
+            Instruction::new_s_type(Opcode::SW, 1, 2, 0),
+            Instruction::new_i_type(Opcode::HALT, 0, 0, 0),
+        ];
+        driver.irq.mask(MSIX_VECTOR_SOFTWARE);
+        driver.submit_kernel(kernel).unwrap();
+        // A clean halt has no MSI-X vector
+        assert_eq!(*log.borrow(), [0]);
+        driver.irq.unmask(MSIX_VECTOR_SOFTWARE);
+        assert_eq!(*log.borrow(), [0, 2]);
+
+        // A fault fires the trap vector
+        driver.submit_kernel(vec![Instruction::new_i_type(Opcode::ECALL, 0, 0, 0)]).unwrap_err();
+        assert_eq!(*log.borrow(), [0, 2, 1]);
+        assert_eq!(driver.bus.read(irq::SWI_BASE, 4), Ok(7));
+    }
+
+    #[test]
+    fn test_launch_returns_before_the_kernel_finishes() {
+        let mut driver = AcceleratorDriver::new();
+        // Count x1 down from 50
+        let kernel = vec![
+            Instruction::new_i_type(Opcode::ADDI, 1, 0, 50),
+            Instruction::new_i_type(Opcode::ADDI, 1, 1, -1),
+            Instruction::new_b_type(Opcode::BNE, 1, 0, -4),
+            Instruction::new_i_type(Opcode::HALT, 0, 0, 0),
+        ];
+        let fence = driver.launch(kernel.clone()).unwrap();
+        assert_eq!(driver.poll(fence), None);
+        assert_eq!(driver.read_status(), DMA_STATUS_BUSY);
+        assert_eq!(driver.launch(kernel).unwrap_err(), SimError::DeviceBusy);
+
+        driver.tick(10);
+        assert!(driver.device_cycles >= 10 && driver.poll(fence).is_none());
+        assert_eq!(driver.wait(fence, 20), Err(SimError::WaitTimeout { cycles: 20 }));
+        assert_eq!(driver.irq_pending(), 0);
+
+        let stats = driver.wait(fence, 1000).unwrap();
+        assert_eq!(stats.instructions, 1 + 2 * 50 + 1);
+        assert_eq!(stats.core_cycles, driver.core.cycle_count);
+        // wait() retired the fence
+        assert_eq!(driver.poll(fence), None);
+        assert_eq!(driver.wait(fence, 1000), Err(SimError::FenceRetired));
+        assert_eq!(driver.irq_pending(), IRQ_CORE_HALT);
+        assert_eq!(driver.read_status(), 0);
+
+        // Idle ticks still advance the clock
+        let now = driver.device_cycles;
+        driver.tick(5);
+        assert_eq!(driver.device_cycles, now + 5);
+    }
+
+    #[test]
+    fn test_mcycle_writes_do_not_move_the_device_clock() {
+        let mut driver = AcceleratorDriver::new();
+        let kernel = vec![
+            Instruction::new_i_type(Opcode::ADDI, 1, 0, 1),
+            Instruction::new_i_type(Opcode::CSRRW, 0, 0, crate::csr::MCYCLE as i32),
+            Instruction::new_i_type(Opcode::HALT, 0, 0, 0),
+        ];
+        let start = driver.device_cycles;
+        let stats = driver.submit_kernel(kernel).unwrap();
+        assert_eq!(stats.core_cycles, 1);
+        assert_eq!(driver.core.elapsed_cycles, 3);
+        assert_eq!(driver.device_cycles - start, 3);
+    }
+
+    #[test]
+    fn test_watchdog_ignores_mcycle_resets() {
+        let mut driver = AcceleratorDriver::new();
+        let kernel = vec![
+            Instruction::new_i_type(Opcode::CSRRW, 0, 0, crate::csr::MCYCLE as i32),
+            Instruction::new_j_type(Opcode::JAL, 0, -4),
+        ];
+        let err = driver.submit_kernel(kernel).unwrap_err();
+        assert_eq!(err, SimError::WatchdogTimeout { cycles: WATCHDOG_CYCLES });
+        assert_eq!(driver.read_status(), DMA_STATUS_ERROR);
+    }
+
+    #[test]
+    fn test_queue_runs_copy_kernel_copy_in_order() {
+        let mut driver = AcceleratorDriver::new();
+        let q = driver.create_queue(0);
+        let done = driver.create_event();
+        // x2 = mem[0x100] * 2 -> mem[0x104]
+        let kernel = vec![
+            Instruction::new_i_type(Opcode::LW, 2, 0, 0x100),
+            Instruction::new_r_type(Opcode::ADD, 2, 2, 2),
+            Instruction::new_s_type(Opcode::SW, 0, 2, 0x104),
+            Instruction::new_i_type(Opcode::HALT, 0, 0, 0),
+        ];
+        driver.enqueue(q, Command::CopyToDevice { addr: 0x100, data: 21i32.to_le_bytes().to_vec() }).unwrap();
+        driver.enqueue(q, Command::LaunchKernel(kernel)).unwrap();
+        driver.enqueue(q, Command::CopyFromDevice { addr: 0x104, len: 4 }).unwrap();
+        driver.enqueue(q, Command::SignalEvent(done)).unwrap();
+        assert_eq!(driver.event_timestamp(done), Ok(None));
+        driver.run_queues();
+
+        let completions = &driver.queues[q].completions;
+        let names: Vec<_> = completions.iter().map(|c| c.command).collect();
+        assert_eq!(names, ["copy-to-device", "launch-kernel", "copy-from-device", "signal-event"]);
+        assert_eq!(completions[2].result, Ok(Output::Data(42i32.to_le_bytes().to_vec())));
+        for pair in completions.windows(2) {
+            assert!(pair[0].times.started <= pair[0].times.completed);
+            assert_eq!(pair[0].times.completed, pair[1].times.started);
+        }
+        assert_eq!(driver.event_timestamp(done), Ok(Some(driver.device_cycles)));
+    }
+
+    #[test]
+    fn test_queue_arbitration_and_barriers() {
+        // Order in which commands from two queues run, as (queue, seq)
+        let order = |arbitration| {
+            let mut driver = AcceleratorDriver::new();
+            driver.arbitration = arbitration;
+            let low = driver.create_queue(0);
+            let high = driver.create_queue(1);
+            for _ in 0..2 {
+                driver.enqueue(low, Command::CopyToDevice { addr: 0, data: vec![0; 4] }).unwrap();
+            }
+            driver.enqueue(high, Command::CopyToDevice { addr: 0, data: vec![0; 4] }).unwrap();
+            driver.enqueue(high, Command::Barrier).unwrap();
+            driver.enqueue(high, Command::CopyToDevice { addr: 0, data: vec![0; 4] }).unwrap();
+            driver.run_queues();
+            let mut runs: Vec<(u64, u64)> = driver
+                .queues
+                .iter()
+                .flat_map(|q| q.completions.iter().map(|c| (c.times.started, c.seq)))
+                .collect();
+            runs.sort();
+            runs.into_iter().map(|(_, seq)| seq).collect::<Vec<_>>()
+        };
+        // The barrier (seq 3) waits for the low queue's seq 0 and 1
+        assert_eq!(order(Arbitration::Fifo), [0, 1, 2, 3, 4]);
+        assert_eq!(order(Arbitration::RoundRobin), [0, 2, 1, 3, 4]);
+        assert_eq!(order(Arbitration::Priority), [2, 0, 1, 3, 4]);
+    }
+
+    #[test]
+    fn test_queue_errors_are_sticky() {
+        let mut driver = AcceleratorDriver::new();
+        let q = driver.create_queue(0);
+        let other = driver.create_queue(0);
+        driver.enqueue(q, Command::CopyFromDevice { addr: 0xFFFF, len: 4 }).unwrap();
+        driver.enqueue(q, Command::LaunchKernel(vec![Instruction::new_i_type(Opcode::HALT, 0, 0, 0)])).unwrap();
+        driver.enqueue(other, Command::CopyToDevice { addr: 0, data: vec![1; 4] }).unwrap();
+        driver.run_queues();
+        let err = SimError::DmaOutOfBounds { addr: 0xFFFF, len: 4 };
+        assert!(driver.queues[q].completions.iter().all(|c| c.result == Err(err.clone())));
+        assert_eq!(driver.queues[other].completions[0].result, Ok(Output::None));
+    }
+
+    #[test]
+    fn test_unknown_queues_and_events_are_rejected() {
+        let mut driver = AcceleratorDriver::new();
+        let q = driver.create_queue(0);
+        // An event from another driver's table
+        let foreign = AcceleratorDriver::new().create_event();
+        assert_eq!(driver.event_timestamp(foreign), Err(SimError::UnknownEvent));
+        assert_eq!(driver.enqueue(q, Command::SignalEvent(foreign)), Err(SimError::UnknownEvent));
+        assert_eq!(driver.enqueue(3, Command::Barrier), Err(SimError::UnknownQueue { queue: 3 }));
+        driver.run_queues();
+        assert!(driver.queues[q].completions.is_empty());
+    }
+
+    #[test]
+    fn test_copy_from_device_and_typed_slices() {
+        let mut driver = AcceleratorDriver::new();
+        driver.write_slice(0x40, &[-1i32, 7, i32::MAX]).unwrap();
+        assert_eq!(driver.read_slice::<i32>(0x40, 3).unwrap(), [-1, 7, i32::MAX]);
+        assert_eq!(driver.read_slice::<u16>(0x44, 2).unwrap(), [7, 0]);
+        // DMA time is accounted: 10 setup cycles per transfer plus one per word
+        assert_eq!(driver.dma_cycles, 2 * 13 + 10 + 1);
+
+        driver.memset_device(0x101, 0xAB, 6).unwrap();
+        let mut out = [0u8; 8];
+        driver.copy_from_device(&mut out, 0x100).unwrap();
+        assert_eq!(out, [0, 0xAB, 0xAB, 0xAB, 0xAB, 0xAB, 0xAB, 0]);
+
+        assert_eq!(
+            driver.copy_from_device(&mut out, 0xFFFC),
+            Err(SimError::DmaOutOfBounds { addr: 0xFFFC, len: 8 })
+        );
+        assert_eq!(driver.read_slice::<f32>(0x10000, 1), Err(SimError::DmaOutOfBounds { addr: 0x10000, len: 4 }));
+
+        // Huge sizes fail the range check instead of overflowing or allocating
+        assert!(driver.read_slice::<u32>(0, usize::MAX / 2).is_err());
+        assert!(driver.memset_device(0, 0, usize::MAX).is_err());
+        assert!(driver.copy_from_device(&mut out, usize::MAX - 2).is_err());
+        assert!(driver.copy_to_device(&out, usize::MAX - 2).is_err());
+    }
+
+    #[test]
+    fn test_launch_abi_arguments_and_return_values() {
+        use crate::builder::KernelBuilder;
+        let mut b = KernelBuilder::new();
+        b.halt(); // Skipped by the entry point
+        b.lw(5, 2, 0); // t0 = 9th argument
+        b.lw(6, 2, 4); // t1 = 10th argument
+        b.add(10, 10, 17); // a0 = a0 + a7
+        b.add(10, 10, 5);
+        b.add(11, 6, 2); // a1 = t1 + sp
+        b.halt();
+        let kernel = b.build().unwrap().instructions;
+
+        let mut driver = AcceleratorDriver::new();
+        let args = KernelArgs::new(&[1, 0, 0, 0, 0, 0, 0, 20, 300, 4000]).with_entry(4);
+        let stats = driver.submit_kernel_with(kernel.clone(), &args).unwrap();
+        // Two spilled words round up to a 16-byte frame
+        assert_eq!(stats.ret, [321, 4000 + STACK_TOP - 16]);
+        assert_eq!(stats.instructions, 6);
+
+        let mut misaligned = KernelArgs::new(&[]);
+        misaligned.stack_top = STACK_TOP - 4;
+        assert_eq!(driver.launch_with(kernel.clone(), &misaligned), Err(SimError::StackOutOfBounds { sp: STACK_TOP - 4 }));
+        let mut low = KernelArgs::new(&[0; 9]);
+        low.stack_top = 0;
+        assert_eq!(driver.launch_with(kernel, &low), Err(SimError::StackOutOfBounds { sp: 0 }));
+    }
+
+    #[test]
+    fn test_particle_kernel_takes_its_buffer_as_arguments() {
+        let mut driver = AcceleratorDriver::new();
+        driver.write_slice(0x200, &[5i32, 6, 7]).unwrap();
+        let stats = driver
+            .submit_kernel_with(crate::kernels::get_particle_sim_kernel(), &KernelArgs::new(&[0x200, 3, 2]))
+            .unwrap();
+        assert_eq!(stats.ret[0], 2);
+        assert_eq!(driver.read_slice::<i32>(0x200, 3).unwrap(), [8, 9, 7]);
     }
 }
@@ -0,0 +1,161 @@
+
+// Simulator Error Types
+// Every fallible simulator/driver operation reports a SimError, which maps
+// onto the hansen_result_t codes of include/hansen_api.h.
+
+use std::fmt;
+
+use crate::isa::DecodeError;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AccessKind {
+    Fetch,
+    Read,
+    Write,
+}
+
+impl fmt::Display for AccessKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            AccessKind::Fetch => "fetch",
+            AccessKind::Read => "read",
+            AccessKind::Write => "write",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SimError {
+    IllegalInstruction { pc: u32, error: DecodeError },
+    // Unimplemented CSR, or a write to a read-only one
+    IllegalCsrAccess { pc: u32, csr: u32, word: u32 },
+    MisalignedAccess { addr: u32, kind: AccessKind },
+    AccessFault { addr: u32, kind: AccessKind },
+    // Only raised when Core::trap_on_divide_by_zero is set; RV32M itself
+    // defines a result for division by zero instead of trapping.
+    DivideByZero { pc: u32 },
+    EnvironmentCall { pc: u32 },
+    Breakpoint { pc: u32 },
+    PcOutOfBounds { pc: u32 },
+    WatchdogTimeout { cycles: u64 },
+    // AcceleratorDriver::wait gave up; the kernel keeps running
+    WaitTimeout { cycles: u64 },
+    // A kernel is already running (the v0 queue is depth-1)
+    DeviceBusy,
+    // The fence's result was already returned by wait()
+    FenceRetired,
+    // Not created by this driver
+    UnknownQueue { queue: usize },
+    UnknownEvent,
+    DmaOutOfBounds { addr: u32, len: usize },
+    // KernelArgs::stack_top is misaligned or outside SRAM, or the spilled
+    // arguments do not fit below it
+    StackOutOfBounds { sp: u32 },
+}
+
+impl fmt::Display for SimError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SimError::IllegalInstruction { pc, error } => write!(f, "Illegal instruction at PC=0x{:08x}: {}", pc, error),
+            SimError::IllegalCsrAccess { pc, csr, .. } => {
+                write!(f, "Illegal access to CSR 0x{:03x} at PC=0x{:08x}", csr, pc)
+            }
+            SimError::MisalignedAccess { addr, kind } => write!(f, "Misaligned {} at 0x{:08x}", kind, addr),
+            SimError::AccessFault { addr, kind } => write!(f, "Memory {} out of bounds: 0x{:08x}", kind, addr),
+            SimError::DivideByZero { pc } => write!(f, "Division by zero at PC=0x{:08x}", pc),
+            SimError::EnvironmentCall { pc } => write!(f, "Environment call at PC=0x{:08x}", pc),
+            SimError::Breakpoint { pc } => write!(f, "Breakpoint at PC=0x{:08x}", pc),
+            SimError::PcOutOfBounds { pc } => write!(f, "PC out of bounds: 0x{:08x}", pc),
+            SimError::WatchdogTimeout { cycles } => write!(f, "Watchdog timer expired after {} cycles", cycles),
+            SimError::WaitTimeout { cycles } => write!(f, "Kernel still running after waiting {} cycles", cycles),
+            SimError::DeviceBusy => write!(f, "Device busy: a kernel is already running"),
+            SimError::FenceRetired => write!(f, "Fence already retired"),
+            SimError::UnknownQueue { queue } => write!(f, "Unknown command queue {}", queue),
+            SimError::UnknownEvent => write!(f, "Unknown event"),
+            SimError::DmaOutOfBounds { addr, len } => {
+                write!(f, "DMA out of bounds: {} bytes at 0x{:08x}", len, addr)
+            }
+            SimError::StackOutOfBounds { sp } => write!(f, "Kernel stack out of bounds: sp=0x{:08x}", sp),
+        }
+    }
+}
+
+impl std::error::Error for SimError {}
+
+// Mirrors hansen_result_t in include/hansen_api.h
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HansenResult {
+    Success = 0,
+    ErrDeviceNotFound = -1,
+    ErrOutOfMemory = -2,
+    ErrInvalidArgument = -3,
+    ErrTimeout = -4,
+    ErrInternal = -5,
+}
+
+impl SimError {
+    pub fn result_code(&self) -> HansenResult {
+        match self {
+            SimError::DmaOutOfBounds { .. }
+            | SimError::StackOutOfBounds { .. }
+            | SimError::FenceRetired
+            | SimError::UnknownQueue { .. }
+            | SimError::UnknownEvent => HansenResult::ErrInvalidArgument,
+            SimError::WatchdogTimeout { .. } | SimError::WaitTimeout { .. } => HansenResult::ErrTimeout,
+            SimError::DeviceBusy => HansenResult::ErrInternal,
+            // Faults raised by the kernel itself are device-side errors
+            SimError::IllegalInstruction { .. }
+            | SimError::IllegalCsrAccess { .. }
+            | SimError::MisalignedAccess { .. }
+            | SimError::AccessFault { .. }
+            | SimError::DivideByZero { .. }
+            | SimError::EnvironmentCall { .. }
+            | SimError::Breakpoint { .. }
+            | SimError::PcOutOfBounds { .. } => HansenResult::ErrInternal,
+        }
+    }
+}
+
+impl<T> From<&Result<T, SimError>> for HansenResult {
+    fn from(result: &Result<T, SimError>) -> Self {
+        match result {
+            Ok(_) => HansenResult::Success,
+            Err(e) => e.result_code(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::isa::DecodeErrorKind;
+
+    #[test]
+    fn test_every_error_maps_to_a_result_code() {
+        let decode = DecodeError { word: 0xFFFF_FFFF, kind: DecodeErrorKind::UnknownOpcode };
+        let cases = [
+            (SimError::IllegalInstruction { pc: 0, error: decode }, HansenResult::ErrInternal),
+            (SimError::IllegalCsrAccess { pc: 0, csr: 0xC00, word: 0 }, HansenResult::ErrInternal),
+            (SimError::MisalignedAccess { addr: 1, kind: AccessKind::Read }, HansenResult::ErrInternal),
+            (SimError::AccessFault { addr: 0, kind: AccessKind::Write }, HansenResult::ErrInternal),
+            (SimError::DivideByZero { pc: 0 }, HansenResult::ErrInternal),
+            (SimError::EnvironmentCall { pc: 0 }, HansenResult::ErrInternal),
+            (SimError::Breakpoint { pc: 0 }, HansenResult::ErrInternal),
+            (SimError::PcOutOfBounds { pc: 0 }, HansenResult::ErrInternal),
+            (SimError::WatchdogTimeout { cycles: 1 }, HansenResult::ErrTimeout),
+            (SimError::WaitTimeout { cycles: 1 }, HansenResult::ErrTimeout),
+            (SimError::DeviceBusy, HansenResult::ErrInternal),
+            (SimError::FenceRetired, HansenResult::ErrInvalidArgument),
+            (SimError::UnknownQueue { queue: 1 }, HansenResult::ErrInvalidArgument),
+            (SimError::UnknownEvent, HansenResult::ErrInvalidArgument),
+            (SimError::DmaOutOfBounds { addr: 0, len: 1 }, HansenResult::ErrInvalidArgument),
+            (SimError::StackOutOfBounds { sp: 0 }, HansenResult::ErrInvalidArgument),
+        ];
+        for (error, code) in cases {
+            assert_eq!(error.result_code(), code, "{:?}", error);
+            assert_eq!(HansenResult::from(&Err::<(), _>(error)), code);
+        }
+        assert_eq!(HansenResult::from(&Ok::<_, SimError>(())), HansenResult::Success);
+    }
+}
@@ -0,0 +1,200 @@
+
+// C ABI (include/hansen_api.h)
+// Implements the hansen_* entry points on top of AcceleratorDriver, so C and
+// C++ hosts can link against libsimulator instead of the PCIe driver. A
+// handle owns one simulated device and must only be used from one thread at
+// a time. hansen_wait_idle converts its timeout to device cycles at
+// DEVICE_CLOCK_HZ.
+
+use std::ffi::{c_int, c_void};
+use std::slice;
+
+use crate::driver::{AcceleratorDriver, Fence};
+use crate::error::{HansenResult, SimError};
+use crate::isa::Instruction;
+
+// Target ASIC clock (MANUAL_EN.md)
+pub const DEVICE_CLOCK_HZ: u64 = 50_000_000;
+
+// The opaque hansen_device_t behind hansen_device_handle_t
+pub struct HansenDevice {
+    pub driver: AcceleratorDriver,
+    fence: Option<Fence>, // Last launched kernel
+}
+
+fn result_code(result: Result<(), SimError>) -> HansenResult {
+    HansenResult::from(&result)
+}
+
+/// Opens simulated device 0.
+///
+/// # Safety
+/// `handle_out` must be null or valid for writing one pointer.
+#[no_mangle]
+pub unsafe extern "C" fn hansen_open(device_id: c_int, handle_out: *mut *mut HansenDevice) -> HansenResult {
+    if handle_out.is_null() {
+        return HansenResult::ErrInvalidArgument;
+    }
+    if device_id != 0 {
+        return HansenResult::ErrDeviceNotFound;
+    }
+    let device = Box::new(HansenDevice { driver: AcceleratorDriver::new(), fence: None });
+    *handle_out = Box::into_raw(device);
+    HansenResult::Success
+}
+
+/// Releases a device opened by `hansen_open`.
+///
+/// # Safety
+/// `handle` must be null or a handle from `hansen_open` that was not closed yet.
+#[no_mangle]
+pub unsafe extern "C" fn hansen_close(handle: *mut HansenDevice) {
+    if !handle.is_null() {
+        drop(Box::from_raw(handle));
+    }
+}
+
+/// # Safety
+/// `handle` must be null or an open handle, and `src_data` must be null or
+/// valid for reading `size_bytes` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn hansen_memcpy_to_device(
+    handle: *mut HansenDevice,
+    dst_addr: u32,
+    src_data: *const c_void,
+    size_bytes: usize,
+) -> HansenResult {
+    let Some(device) = handle.as_mut() else { return HansenResult::ErrInvalidArgument };
+    if src_data.is_null() {
+        return HansenResult::ErrInvalidArgument;
+    }
+    // Checked before the slice is built, so an oversized length never
+    // describes more memory than the caller can own
+    if let Err(e) = device.driver.check_range(dst_addr as usize, size_bytes) {
+        return e.result_code();
+    }
+    let data = slice::from_raw_parts(src_data as *const u8, size_bytes);
+    result_code(device.driver.copy_to_device(data, dst_addr as usize))
+}
+
+/// # Safety
+/// `handle` must be null or an open handle, and `dst_data` must be null or
+/// valid for writing `size_bytes` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn hansen_memcpy_from_device(
+    handle: *mut HansenDevice,
+    dst_data: *mut c_void,
+    src_addr: u32,
+    size_bytes: usize,
+) -> HansenResult {
+    let Some(device) = handle.as_mut() else { return HansenResult::ErrInvalidArgument };
+    if dst_data.is_null() {
+        return HansenResult::ErrInvalidArgument;
+    }
+    if let Err(e) = device.driver.check_range(src_addr as usize, size_bytes) {
+        return e.result_code();
+    }
+    let out = slice::from_raw_parts_mut(dst_data as *mut u8, size_bytes);
+    result_code(device.driver.copy_from_device(out, src_addr as usize))
+}
+
+/// Decodes the kernel and starts it; returns without waiting. Fails with
+/// HANSEN_ERR_INVALID_ARGUMENT on an undecodable word or a `count` no buffer
+/// can hold.
+///
+/// # Safety
+/// `handle` must be null or an open handle, and `instructions` must be null
+/// or valid for reading `count` words.
+#[no_mangle]
+pub unsafe extern "C" fn hansen_launch_kernel(
+    handle: *mut HansenDevice,
+    instructions: *const u32,
+    count: usize,
+) -> HansenResult {
+    let Some(device) = handle.as_mut() else { return HansenResult::ErrInvalidArgument };
+    if instructions.is_null() || count > isize::MAX as usize / size_of::<u32>() {
+        return HansenResult::ErrInvalidArgument;
+    }
+    let words = slice::from_raw_parts(instructions, count);
+    let Ok(kernel) = words.iter().map(|&w| Instruction::decode(w)).collect::<Result<Vec<_>, _>>() else {
+        return HansenResult::ErrInvalidArgument;
+    };
+    match device.driver.launch(kernel) {
+        Ok(fence) => {
+            device.fence = Some(fence);
+            HansenResult::Success
+        }
+        Err(e) => e.result_code(),
+    }
+}
+
+/// Runs the device until the last launched kernel finishes, returning its
+/// fault as an error code. Succeeds at once if nothing was launched or the
+/// kernel was already waited for.
+///
+/// # Safety
+/// `handle` must be null or an open handle.
+#[no_mangle]
+pub unsafe extern "C" fn hansen_wait_idle(handle: *mut HansenDevice, timeout_ms: u32) -> HansenResult {
+    let Some(device) = handle.as_mut() else { return HansenResult::ErrInvalidArgument };
+    let Some(fence) = device.fence else { return HansenResult::Success };
+    let timeout = timeout_ms as u64 * (DEVICE_CLOCK_HZ / 1000);
+    let result = device.driver.wait(fence, timeout);
+    // The fence is retired unless the kernel is still running
+    if !matches!(result, Err(SimError::WaitTimeout { .. })) {
+        device.fence = None;
+    }
+    result_code(result.map(|_| ()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ptr;
+
+    #[test]
+    fn test_round_trip_through_the_c_abi() {
+        unsafe {
+            let mut dev = ptr::null_mut();
+            assert_eq!(hansen_open(0, ptr::null_mut()), HansenResult::ErrInvalidArgument);
+            assert_eq!(hansen_open(1, &mut dev), HansenResult::ErrDeviceNotFound);
+            assert_eq!(hansen_open(0, &mut dev), HansenResult::Success);
+
+            let input: [i32; 2] = [20, 22];
+            assert_eq!(hansen_memcpy_to_device(dev, 0x100, input.as_ptr().cast(), 8), HansenResult::Success);
+            assert_eq!(hansen_memcpy_to_device(dev, 65530, input.as_ptr().cast(), 8), HansenResult::ErrInvalidArgument);
+            assert_eq!(hansen_memcpy_to_device(dev, 0, ptr::null(), 4), HansenResult::ErrInvalidArgument);
+            // Oversized lengths are rejected before the host buffer is touched
+            assert_eq!(hansen_memcpy_to_device(dev, 0, input.as_ptr().cast(), usize::MAX), HansenResult::ErrInvalidArgument);
+            assert_eq!(hansen_memcpy_to_device(dev, 4, input.as_ptr().cast(), 0x10000), HansenResult::ErrInvalidArgument);
+
+            // mem[0x108] = mem[0x100] + mem[0x104]
+            let kernel: [u32; 5] = [
+                0x10002083, // lw   x1, 0x100(x0)
+                0x10402103, // lw   x2, 0x104(x0)
+                0x002081B3, // add  x3, x1, x2
+                0x10302423, // sw   x3, 0x108(x0)
+                0x0000007B, // halt
+            ];
+            assert_eq!(hansen_wait_idle(dev, 0), HansenResult::Success);
+            assert_eq!(hansen_launch_kernel(dev, kernel.as_ptr(), kernel.len()), HansenResult::Success);
+            assert_eq!(hansen_launch_kernel(dev, kernel.as_ptr(), kernel.len()), HansenResult::ErrInternal);
+            assert_eq!(hansen_wait_idle(dev, 10), HansenResult::Success);
+            assert_eq!(hansen_wait_idle(dev, 10), HansenResult::Success);
+
+            let mut sum = 0i32;
+            assert_eq!(hansen_memcpy_from_device(dev, (&mut sum as *mut i32).cast(), 0x108, 4), HansenResult::Success);
+            assert_eq!(sum, 42);
+            let out = (&mut sum as *mut i32).cast();
+            assert_eq!(hansen_memcpy_from_device(dev, out, 0, isize::MAX as usize + 1), HansenResult::ErrInvalidArgument);
+
+            // A faulting kernel reports through wait_idle
+            let bad: [u32; 1] = [0x00002083]; // lw x1, 0(x0), then PC runs off the end
+            assert_eq!(hansen_launch_kernel(dev, bad.as_ptr(), 1), HansenResult::Success);
+            assert_eq!(hansen_wait_idle(dev, 10), HansenResult::ErrInternal);
+            assert_eq!(hansen_launch_kernel(dev, [0xFFFF_FFFFu32].as_ptr(), 1), HansenResult::ErrInvalidArgument);
+            assert_eq!(hansen_launch_kernel(dev, kernel.as_ptr(), usize::MAX / 4), HansenResult::ErrInvalidArgument);
+            hansen_close(dev);
+        }
+    }
+}
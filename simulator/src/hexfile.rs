@@ -0,0 +1,51 @@
+
+// Hex program files
+// One 32-bit word per line in hexadecimal, with optional "//" comments,
+// as consumed by the Verilog testbenches ($readmemh) and the simulator tools.
+
+use std::fs;
+
+pub fn parse_hex(text: &str) -> Result<Vec<u32>, String> {
+    let mut words = Vec::new();
+    for (line_no, line) in text.lines().enumerate() {
+        let hex_str = line.split("//").next().unwrap_or("").trim();
+        if hex_str.is_empty() {
+            continue;
+        }
+        match u32::from_str_radix(hex_str, 16) {
+            Ok(word) => words.push(word),
+            Err(_) => return Err(format!("line {}: invalid hex word '{}'", line_no + 1, hex_str)),
+        }
+    }
+    Ok(words)
+}
+
+pub fn read_hex_file(path: &str) -> Result<Vec<u32>, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    parse_hex(&text).map_err(|e| format!("{}: {}", path, e))
+}
+
+// Inverse of `parse_hex`: one zero-padded word per line
+pub fn format_hex(words: &[u32]) -> String {
+    words.iter().map(|w| format!("{:08X}\n", w)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_then_parse_round_trips() {
+        let words = [0x0000_0013, 0xDEAD_BEEF, 0];
+        assert_eq!(format_hex(&words), "00000013\nDEADBEEF\n00000000\n");
+        assert_eq!(parse_hex(&format_hex(&words)).unwrap(), words);
+        assert_eq!(parse_hex("  13 // nop\n\n// only a comment\nff\n").unwrap(), [0x13, 0xFF]);
+    }
+
+    #[test]
+    fn test_malformed_lines_report_their_line_number() {
+        assert_eq!(parse_hex("13\nxyz\n"), Err("line 2: invalid hex word 'xyz'".to_string()));
+        assert_eq!(parse_hex("\n\n1_0000_0000").unwrap_err(), "line 3: invalid hex word '1_0000_0000'");
+        assert_eq!(parse_hex("100000000").unwrap_err(), "line 1: invalid hex word '100000000'");
+    }
+}
@@ -0,0 +1,233 @@
+
+// Interrupt Controller (HARDWARE_INTERFACE_v0.md, HARDWARE_INTERFACE_future.md section 2)
+// Delivers device interrupt lines to host-side handlers, either as the v0
+// level-triggered INTx line or as one MSI-X message per source. MSIX_LINES
+// maps each vector to its line; IRQ_CORE_HALT has no vector and is only seen
+// through INTx.
+// A masked vector latches its pending bit and fires when unmasked.
+// Registering, masking or unmasking a vector past MSIX_VECTORS does nothing.
+
+use crate::bus::Device;
+use crate::error::SimError;
+use crate::memory::Memory;
+
+// Interrupt lines, as returned by Device::tick
+pub const IRQ_DMA_COMPLETE: u32 = 1 << 0;
+pub const IRQ_CORE_HALT: u32 = 1 << 1; // Also asserted when the core traps
+pub const IRQ_SOFTWARE: u32 = 1 << 2; // Kernel wrote the SWI doorbell
+pub const IRQ_CORE_TRAP: u32 = 1 << 3; // Core fault or watchdog expiry
+
+pub const MSIX_VECTORS: usize = 3;
+pub const MSIX_VECTOR_DMA: usize = 0;
+pub const MSIX_VECTOR_CORE: usize = 1;
+pub const MSIX_VECTOR_SOFTWARE: usize = 2;
+// Line delivered by each vector (HARDWARE_INTERFACE_future.md section 2)
+pub const MSIX_LINES: [u32; MSIX_VECTORS] = [IRQ_DMA_COMPLETE, IRQ_CORE_TRAP, IRQ_SOFTWARE];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IrqMode {
+    Intx, // v0: one shared level-triggered line
+    MsiX, // v2.0: one message per vector
+}
+
+// INTx handlers get the asserted lines, MSI-X handlers the vector number
+pub type IrqHandler = Box<dyn FnMut(u32)>;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct VectorState {
+    pub masked: bool,
+    pub pending: bool,
+    pub delivered: u64,
+}
+
+pub struct InterruptController {
+    pub mode: IrqMode,
+    pub lines: u32, // Sources asserted since the last ack, in both modes
+    pub intx_masked: bool,
+    pub vectors: [VectorState; MSIX_VECTORS],
+    intx_handler: Option<IrqHandler>,
+    handlers: [Option<IrqHandler>; MSIX_VECTORS],
+}
+
+impl InterruptController {
+    pub fn new(mode: IrqMode) -> Self {
+        InterruptController {
+            mode,
+            lines: 0,
+            intx_masked: false,
+            vectors: [VectorState::default(); MSIX_VECTORS],
+            intx_handler: None,
+            handlers: [None, None, None],
+        }
+    }
+
+    pub fn on_intx(&mut self, handler: IrqHandler) {
+        self.intx_handler = Some(handler);
+    }
+
+    pub fn on_vector(&mut self, vector: usize, handler: IrqHandler) {
+        if let Some(slot) = self.handlers.get_mut(vector) {
+            *slot = Some(handler);
+        }
+    }
+
+    // Asserts `lines` and delivers them according to the mode
+    pub fn raise(&mut self, lines: u32) {
+        if lines == 0 {
+            return;
+        }
+        self.lines |= lines;
+        match self.mode {
+            IrqMode::Intx => self.deliver_intx(),
+            IrqMode::MsiX => {
+                for vector in (0..MSIX_VECTORS).filter(|&v| lines & MSIX_LINES[v] != 0) {
+                    if self.vectors[vector].masked {
+                        self.vectors[vector].pending = true;
+                    } else {
+                        self.deliver(vector);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn ack(&mut self, lines: u32) {
+        self.lines &= !lines;
+    }
+
+    pub fn mask(&mut self, vector: usize) {
+        if let Some(state) = self.vectors.get_mut(vector) {
+            state.masked = true;
+        }
+    }
+
+    // Fires a vector that was raised while it was masked
+    pub fn unmask(&mut self, vector: usize) {
+        let Some(state) = self.vectors.get_mut(vector) else { return };
+        state.masked = false;
+        if std::mem::take(&mut state.pending) {
+            self.deliver(vector);
+        }
+    }
+
+    // The line is level-triggered: unmasking with a source still asserted
+    // interrupts again
+    pub fn set_intx_masked(&mut self, masked: bool) {
+        self.intx_masked = masked;
+        if !masked && self.mode == IrqMode::Intx {
+            self.deliver_intx();
+        }
+    }
+
+    fn deliver_intx(&mut self) {
+        if self.intx_masked || self.lines == 0 {
+            return;
+        }
+        if let Some(handler) = self.intx_handler.as_mut() {
+            handler(self.lines);
+        }
+    }
+
+    fn deliver(&mut self, vector: usize) {
+        self.vectors[vector].delivered += 1;
+        if let Some(handler) = self.handlers[vector].as_mut() {
+            handler(vector as u32);
+        }
+    }
+}
+
+pub const SWI_BASE: u32 = 0x4000_1000;
+pub const SWI_REGS_SIZE: u32 = 4;
+
+// Doorbell for kernels: a write raises IRQ_SOFTWARE on the next bus tick and
+// leaves the written value for the host to read back
+#[derive(Debug, Default)]
+pub struct SoftwareInterrupt {
+    pub value: u32,
+    rung: bool,
+}
+
+impl Device for SoftwareInterrupt {
+    fn name(&self) -> &str {
+        "swi"
+    }
+
+    fn read(&mut self, _offset: u32, _size: u32) -> Result<u32, SimError> {
+        Ok(self.value)
+    }
+
+    fn write(&mut self, _offset: u32, _size: u32, value: u32) -> Result<(), SimError> {
+        self.value = value;
+        self.rung = true;
+        Ok(())
+    }
+
+    fn tick(&mut self, _cycles: u64, _ram: &mut Memory) -> u32 {
+        if std::mem::take(&mut self.rung) { IRQ_SOFTWARE } else { 0 }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn recorder(log: &Rc<RefCell<Vec<u32>>>) -> IrqHandler {
+        let log = log.clone();
+        Box::new(move |v| log.borrow_mut().push(v))
+    }
+
+    #[test]
+    fn test_msix_vectors_and_masking() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut irq = InterruptController::new(IrqMode::MsiX);
+        for vector in 0..MSIX_VECTORS {
+            irq.on_vector(vector, recorder(&log));
+        }
+        irq.raise(IRQ_DMA_COMPLETE | IRQ_CORE_HALT | IRQ_CORE_TRAP);
+        assert_eq!(*log.borrow(), [0, 1]);
+        irq.raise(IRQ_CORE_HALT); // INTx only
+        assert_eq!(log.borrow().len(), 2);
+
+        irq.mask(MSIX_VECTOR_SOFTWARE);
+        irq.raise(IRQ_SOFTWARE);
+        irq.raise(IRQ_SOFTWARE);
+        assert!(irq.vectors[MSIX_VECTOR_SOFTWARE].pending);
+        assert_eq!(log.borrow().len(), 2);
+        // Both raises collapse into one message
+        irq.unmask(MSIX_VECTOR_SOFTWARE);
+        assert_eq!(*log.borrow(), [0, 1, 2]);
+        assert_eq!(irq.vectors[MSIX_VECTOR_SOFTWARE], VectorState { masked: false, pending: false, delivered: 1 });
+    }
+
+    #[test]
+    fn test_unknown_vectors_are_ignored() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut irq = InterruptController::new(IrqMode::MsiX);
+        irq.on_vector(MSIX_VECTORS, recorder(&log));
+        irq.mask(MSIX_VECTORS);
+        irq.unmask(usize::MAX);
+        irq.raise(IRQ_DMA_COMPLETE | IRQ_CORE_TRAP | IRQ_SOFTWARE);
+        assert!(log.borrow().is_empty());
+        // The real vectors were left unmasked and without handlers
+        assert!(irq.vectors.iter().all(|v| !v.masked && v.delivered == 1));
+    }
+
+    #[test]
+    fn test_intx_is_level_triggered() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut irq = InterruptController::new(IrqMode::Intx);
+        irq.on_intx(recorder(&log));
+        irq.set_intx_masked(true);
+        irq.raise(IRQ_DMA_COMPLETE);
+        assert!(log.borrow().is_empty());
+        irq.set_intx_masked(false);
+        irq.raise(IRQ_CORE_HALT);
+        assert_eq!(*log.borrow(), [IRQ_DMA_COMPLETE, IRQ_DMA_COMPLETE | IRQ_CORE_HALT]);
+
+        irq.ack(IRQ_DMA_COMPLETE | IRQ_CORE_HALT);
+        irq.set_intx_masked(false);
+        assert_eq!(log.borrow().len(), 2);
+    }
+}
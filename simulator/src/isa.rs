@@ -1,24 +1,166 @@
-
 // Instruction Set Architecture (ISA) Definition
-// Based on a simplified RISC-V (RV32I) subset
+// RISC-V RV32I base integer set plus the Hansen custom HALT
+
+use std::fmt;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Opcode {
+    // Register-register ALU (R-Type)
     ADD,
     SUB,
-    MUL, // Optional M-extension
+    SLL,  // Shift Left Logical
+    SLT,  // Set Less Than (signed)
+    SLTU, // Set Less Than Unsigned
+    XOR,
+    SRL,  // Shift Right Logical
+    SRA,  // Shift Right Arithmetic
+    OR,
+    AND,
+    // M-extension (funct7 = 0000001)
+    MUL,
+    MULH,   // High 32 bits, signed x signed
+    MULHSU, // High 32 bits, signed x unsigned
+    MULHU,  // High 32 bits, unsigned x unsigned
     DIV,
+    DIVU,
+    REM,
+    REMU,
+    // Register-immediate ALU (I-Type)
     ADDI,
+    SLTI,
+    SLTIU,
+    XORI,
+    ORI,
+    ANDI,
+    SLLI, // imm holds shamt
+    SRLI,
+    SRAI,
+    LB,  // Load Byte (sign-extended)
+    LH,  // Load Halfword (sign-extended)
     LW,  // Load Word
+    LBU, // Load Byte Unsigned
+    LHU, // Load Halfword Unsigned
+    SB,  // Store Byte
+    SH,  // Store Halfword
     SW,  // Store Word
     BEQ, // Branch if Equal
     BNE, // Branch if Not Equal
-    JAL, // Jump and Link
+    BLT, // Branch if Less Than (signed)
+    BGE, // Branch if Greater or Equal (signed)
+    BLTU,
+    BGEU,
+    JAL,  // Jump and Link
+    JALR, // Jump and Link Register
+    LUI,   // Load Upper Immediate (imm holds the already shifted value)
+    AUIPC, // Add Upper Immediate to PC
+    FENCE,
+    ECALL,
+    EBREAK,
+    MRET, // Return from machine-mode trap handler
+    // Zicsr: imm holds the 12-bit CSR address, the *I forms keep zimm in rs1
+    CSRRW,
+    CSRRS,
+    CSRRC,
+    CSRRWI,
+    CSRRSI,
+    CSRRCI,
     HALT, // Custom instruction to stop execution
-    UNKNOWN,
 }
 
-#[derive(Debug, Clone, Copy)]
+// Standard RISC-V calling-convention names for x0..x31
+pub const ABI_NAMES: [&str; 32] = [
+    "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2",
+    "s0", "s1", "a0", "a1", "a2", "a3", "a4", "a5",
+    "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7",
+    "s8", "s9", "s10", "s11", "t3", "t4", "t5", "t6",
+];
+
+impl Opcode {
+    // Every opcode, in encoding-table order
+    pub const ALL: [Opcode; 56] = [
+        Opcode::ADD, Opcode::SUB, Opcode::SLL, Opcode::SLT, Opcode::SLTU, Opcode::XOR,
+        Opcode::SRL, Opcode::SRA, Opcode::OR, Opcode::AND,
+        Opcode::MUL, Opcode::MULH, Opcode::MULHSU, Opcode::MULHU,
+        Opcode::DIV, Opcode::DIVU, Opcode::REM, Opcode::REMU,
+        Opcode::ADDI, Opcode::SLTI, Opcode::SLTIU, Opcode::XORI, Opcode::ORI, Opcode::ANDI,
+        Opcode::SLLI, Opcode::SRLI, Opcode::SRAI,
+        Opcode::LB, Opcode::LH, Opcode::LW, Opcode::LBU, Opcode::LHU,
+        Opcode::SB, Opcode::SH, Opcode::SW,
+        Opcode::BEQ, Opcode::BNE, Opcode::BLT, Opcode::BGE, Opcode::BLTU, Opcode::BGEU,
+        Opcode::JAL, Opcode::JALR, Opcode::LUI, Opcode::AUIPC,
+        Opcode::FENCE, Opcode::ECALL, Opcode::EBREAK, Opcode::MRET,
+        Opcode::CSRRW, Opcode::CSRRS, Opcode::CSRRC, Opcode::CSRRWI, Opcode::CSRRSI, Opcode::CSRRCI,
+        Opcode::HALT,
+    ];
+
+    pub fn from_mnemonic(name: &str) -> Option<Opcode> {
+        let name = name.to_ascii_lowercase();
+        Opcode::ALL.iter().copied().find(|op| op.mnemonic() == name)
+    }
+
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Opcode::ADD => "add",
+            Opcode::SUB => "sub",
+            Opcode::SLL => "sll",
+            Opcode::SLT => "slt",
+            Opcode::SLTU => "sltu",
+            Opcode::XOR => "xor",
+            Opcode::SRL => "srl",
+            Opcode::SRA => "sra",
+            Opcode::OR => "or",
+            Opcode::AND => "and",
+            Opcode::MUL => "mul",
+            Opcode::MULH => "mulh",
+            Opcode::MULHSU => "mulhsu",
+            Opcode::MULHU => "mulhu",
+            Opcode::DIV => "div",
+            Opcode::DIVU => "divu",
+            Opcode::REM => "rem",
+            Opcode::REMU => "remu",
+            Opcode::ADDI => "addi",
+            Opcode::SLTI => "slti",
+            Opcode::SLTIU => "sltiu",
+            Opcode::XORI => "xori",
+            Opcode::ORI => "ori",
+            Opcode::ANDI => "andi",
+            Opcode::SLLI => "slli",
+            Opcode::SRLI => "srli",
+            Opcode::SRAI => "srai",
+            Opcode::LB => "lb",
+            Opcode::LH => "lh",
+            Opcode::LW => "lw",
+            Opcode::LBU => "lbu",
+            Opcode::LHU => "lhu",
+            Opcode::SB => "sb",
+            Opcode::SH => "sh",
+            Opcode::SW => "sw",
+            Opcode::BEQ => "beq",
+            Opcode::BNE => "bne",
+            Opcode::BLT => "blt",
+            Opcode::BGE => "bge",
+            Opcode::BLTU => "bltu",
+            Opcode::BGEU => "bgeu",
+            Opcode::JAL => "jal",
+            Opcode::JALR => "jalr",
+            Opcode::LUI => "lui",
+            Opcode::AUIPC => "auipc",
+            Opcode::FENCE => "fence",
+            Opcode::ECALL => "ecall",
+            Opcode::EBREAK => "ebreak",
+            Opcode::MRET => "mret",
+            Opcode::CSRRW => "csrrw",
+            Opcode::CSRRS => "csrrs",
+            Opcode::CSRRC => "csrrc",
+            Opcode::CSRRWI => "csrrwi",
+            Opcode::CSRRSI => "csrrsi",
+            Opcode::CSRRCI => "csrrci",
+            Opcode::HALT => "halt",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Instruction {
     pub opcode: Opcode,
     pub rd:  usize, // Destination register
@@ -49,13 +191,59 @@ impl Instruction {
         Instruction { opcode, rd, rs1: 0, rs2: 0, imm }
     }
 
-    pub fn decode(word: u32) -> Self {
+    pub fn new_u_type(opcode: Opcode, rd: usize, imm: i32) -> Self {
+        // imm is the final register value contribution, i.e. imm[31:12] << 12
+        Instruction { opcode, rd, rs1: 0, rs2: 0, imm }
+    }
+
+    // Registers read by this instruction (x0 excluded), for hazard detection
+    pub fn source_regs(&self) -> [Option<usize>; 2] {
+        let nonzero = |r: usize| if r == 0 { None } else { Some(r) };
+        match self.opcode {
+            Opcode::ADD | Opcode::SUB | Opcode::SLL | Opcode::SLT | Opcode::SLTU | Opcode::XOR
+            | Opcode::SRL | Opcode::SRA | Opcode::OR | Opcode::AND | Opcode::MUL | Opcode::MULH
+            | Opcode::MULHSU | Opcode::MULHU | Opcode::DIV | Opcode::DIVU | Opcode::REM
+            | Opcode::REMU | Opcode::SB | Opcode::SH | Opcode::SW | Opcode::BEQ | Opcode::BNE
+            | Opcode::BLT | Opcode::BGE | Opcode::BLTU | Opcode::BGEU => {
+                [nonzero(self.rs1), nonzero(self.rs2)]
+            }
+            Opcode::ADDI | Opcode::SLTI | Opcode::SLTIU | Opcode::XORI | Opcode::ORI | Opcode::ANDI
+            | Opcode::SLLI | Opcode::SRLI | Opcode::SRAI | Opcode::LB | Opcode::LH | Opcode::LW
+            | Opcode::LBU | Opcode::LHU | Opcode::JALR | Opcode::CSRRW | Opcode::CSRRS
+            | Opcode::CSRRC => [nonzero(self.rs1), None],
+            Opcode::JAL | Opcode::LUI | Opcode::AUIPC | Opcode::FENCE | Opcode::ECALL
+            | Opcode::EBREAK | Opcode::MRET | Opcode::CSRRWI | Opcode::CSRRSI | Opcode::CSRRCI
+            | Opcode::HALT => [None, None],
+        }
+    }
+
+    // Register written by this instruction, if any (never x0)
+    pub fn dest_reg(&self) -> Option<usize> {
+        match self.opcode {
+            Opcode::SB | Opcode::SH | Opcode::SW | Opcode::BEQ | Opcode::BNE | Opcode::BLT
+            | Opcode::BGE | Opcode::BLTU | Opcode::BGEU | Opcode::FENCE | Opcode::ECALL
+            | Opcode::EBREAK | Opcode::MRET | Opcode::HALT => None,
+            _ if self.rd == 0 => None,
+            _ => Some(self.rd),
+        }
+    }
+
+    pub fn is_load(&self) -> bool {
+        matches!(self.opcode, Opcode::LB | Opcode::LH | Opcode::LW | Opcode::LBU | Opcode::LHU)
+    }
+
+    pub fn is_store(&self) -> bool {
+        matches!(self.opcode, Opcode::SB | Opcode::SH | Opcode::SW)
+    }
+
+    pub fn decode(word: u32) -> Result<Self, DecodeError> {
+        let illegal = |kind| Err(DecodeError { word, kind });
         let opcode_bits = word & 0x7F;
         let rd  = ((word >> 7) & 0x1F) as usize;
         let funct3 = (word >> 12) & 0x7;
         let rs1 = ((word >> 15) & 0x1F) as usize;
         let rs2 = ((word >> 20) & 0x1F) as usize;
-        // let funct7 = (word >> 25) & 0x7F;
+        let funct7 = (word >> 25) & 0x7F;
         
         // I-Type Imm
         let imm_i = (word as i32) >> 20; 
@@ -79,24 +267,456 @@ impl Instruction {
         let imm_j_raw = (j_bit_31 << 20) | (j_bit_19_12 << 12) | (j_bit_20 << 11) | (j_bit_30_21 << 1);
         let imm_j = (imm_j_raw as i32) << 11 >> 11; // Sign extend 21 bits
 
-        match opcode_bits {
+        // U-Type Imm
+        let imm_u = (word & 0xFFFF_F000) as i32;
+
+        let instr = match opcode_bits {
             0x33 => { // R-Type 0110011
-                let bit_30 = (word >> 30) & 1;
-                // Check bit 30 for SUB
-                if bit_30 == 1 { Instruction::new_r_type(Opcode::SUB, rd, rs1, rs2) }
-                else { Instruction::new_r_type(Opcode::ADD, rd, rs1, rs2) }
-                // MUL is not standard R-Type check without funct7 check, simplification here.
+                let op = match (funct7, funct3) {
+                    (0x00, 0x0) => Opcode::ADD,
+                    (0x20, 0x0) => Opcode::SUB,
+                    (0x00, 0x1) => Opcode::SLL,
+                    (0x00, 0x2) => Opcode::SLT,
+                    (0x00, 0x3) => Opcode::SLTU,
+                    (0x00, 0x4) => Opcode::XOR,
+                    (0x00, 0x5) => Opcode::SRL,
+                    (0x20, 0x5) => Opcode::SRA,
+                    (0x00, 0x6) => Opcode::OR,
+                    (0x00, 0x7) => Opcode::AND,
+                    (0x01, 0x0) => Opcode::MUL,
+                    (0x01, 0x1) => Opcode::MULH,
+                    (0x01, 0x2) => Opcode::MULHSU,
+                    (0x01, 0x3) => Opcode::MULHU,
+                    (0x01, 0x4) => Opcode::DIV,
+                    (0x01, 0x5) => Opcode::DIVU,
+                    (0x01, 0x6) => Opcode::REM,
+                    (0x01, 0x7) => Opcode::REMU,
+                    _ => return illegal(DecodeErrorKind::ReservedFunct7),
+                };
+                Instruction::new_r_type(op, rd, rs1, rs2)
+            },
+            0x13 => { // I-Type ALU 0010011
+                let shamt = ((word >> 20) & 0x1F) as i32;
+                match (funct3, funct7) {
+                    (0x0, _) => Instruction::new_i_type(Opcode::ADDI, rd, rs1, imm_i),
+                    (0x2, _) => Instruction::new_i_type(Opcode::SLTI, rd, rs1, imm_i),
+                    (0x3, _) => Instruction::new_i_type(Opcode::SLTIU, rd, rs1, imm_i),
+                    (0x4, _) => Instruction::new_i_type(Opcode::XORI, rd, rs1, imm_i),
+                    (0x6, _) => Instruction::new_i_type(Opcode::ORI, rd, rs1, imm_i),
+                    (0x7, _) => Instruction::new_i_type(Opcode::ANDI, rd, rs1, imm_i),
+                    // Shifts reuse imm[11:5] as funct7; shamt[5] must be 0 on RV32
+                    (0x1, 0x00) => Instruction::new_i_type(Opcode::SLLI, rd, rs1, shamt),
+                    (0x5, 0x00) => Instruction::new_i_type(Opcode::SRLI, rd, rs1, shamt),
+                    (0x5, 0x20) => Instruction::new_i_type(Opcode::SRAI, rd, rs1, shamt),
+                    _ => return illegal(DecodeErrorKind::ReservedFunct7),
+                }
+            },
+            0x03 => match funct3 { // Load 0000011
+                0x0 => Instruction::new_i_type(Opcode::LB, rd, rs1, imm_i),
+                0x1 => Instruction::new_i_type(Opcode::LH, rd, rs1, imm_i),
+                0x2 => Instruction::new_i_type(Opcode::LW, rd, rs1, imm_i),
+                0x4 => Instruction::new_i_type(Opcode::LBU, rd, rs1, imm_i),
+                0x5 => Instruction::new_i_type(Opcode::LHU, rd, rs1, imm_i),
+                _ => return illegal(DecodeErrorKind::ReservedFunct3),
+            },
+            0x23 => match funct3 { // Store 0100011
+                0x0 => Instruction::new_s_type(Opcode::SB, rs1, rs2, imm_s),
+                0x1 => Instruction::new_s_type(Opcode::SH, rs1, rs2, imm_s),
+                0x2 => Instruction::new_s_type(Opcode::SW, rs1, rs2, imm_s),
+                _ => return illegal(DecodeErrorKind::ReservedFunct3),
+            },
+            0x63 => { // Branch 1100011
+                let op = match funct3 {
+                    0x0 => Opcode::BEQ,
+                    0x1 => Opcode::BNE,
+                    0x4 => Opcode::BLT,
+                    0x5 => Opcode::BGE,
+                    0x6 => Opcode::BLTU,
+                    0x7 => Opcode::BGEU,
+                    _ => return illegal(DecodeErrorKind::ReservedFunct3),
+                };
+                if imm_b & 0x3 != 0 {
+                    return illegal(DecodeErrorKind::MisalignedImmediate);
+                }
+                Instruction::new_b_type(op, rs1, rs2, imm_b)
+            },
+            0x6F => {
+                if imm_j & 0x3 != 0 {
+                    return illegal(DecodeErrorKind::MisalignedImmediate);
+                }
+                Instruction::new_j_type(Opcode::JAL, rd, imm_j)
             },
-            0x13 => Instruction::new_i_type(Opcode::ADDI, rd, rs1, imm_i),
-            0x03 => Instruction::new_i_type(Opcode::LW, rd, rs1, imm_i),
-            0x23 => Instruction::new_s_type(Opcode::SW, rs1, rs2, imm_s),
-            0x63 => { // Branch
-                if funct3 == 0 { Instruction::new_b_type(Opcode::BEQ, rs1, rs2, imm_b) }
-                else { Instruction::new_b_type(Opcode::BNE, rs1, rs2, imm_b) }
+            0x67 => match funct3 { // JALR 1100111
+                0x0 => Instruction::new_i_type(Opcode::JALR, rd, rs1, imm_i),
+                _ => return illegal(DecodeErrorKind::ReservedFunct3),
             },
-            0x6F => Instruction::new_j_type(Opcode::JAL, rd, imm_j),
-            0x7B => Instruction::new_i_type(Opcode::HALT, 0, 0, 0), // Custom
-            _ => Instruction { opcode: Opcode::UNKNOWN, rd:0, rs1:0, rs2:0, imm:0 }
+            0x37 => Instruction::new_u_type(Opcode::LUI, rd, imm_u),
+            0x17 => Instruction::new_u_type(Opcode::AUIPC, rd, imm_u),
+            0x0F => match funct3 { // MISC-MEM; pred/succ are kept in imm
+                0x0 => Instruction::new_i_type(Opcode::FENCE, rd, rs1, imm_i),
+                _ => return illegal(DecodeErrorKind::ReservedFunct3),
+            },
+            0x73 => { // SYSTEM
+                let csr = ((word >> 20) & 0xFFF) as i32;
+                match funct3 {
+                    0x0 => match word {
+                        0x0000_0073 => Instruction::new_i_type(Opcode::ECALL, 0, 0, 0),
+                        0x0010_0073 => Instruction::new_i_type(Opcode::EBREAK, 0, 0, 1),
+                        0x3020_0073 => Instruction::new_i_type(Opcode::MRET, 0, 0, 0x302),
+                        _ => return illegal(DecodeErrorKind::ReservedEncoding),
+                    },
+                    0x1 => Instruction::new_i_type(Opcode::CSRRW, rd, rs1, csr),
+                    0x2 => Instruction::new_i_type(Opcode::CSRRS, rd, rs1, csr),
+                    0x3 => Instruction::new_i_type(Opcode::CSRRC, rd, rs1, csr),
+                    0x5 => Instruction::new_i_type(Opcode::CSRRWI, rd, rs1, csr),
+                    0x6 => Instruction::new_i_type(Opcode::CSRRSI, rd, rs1, csr),
+                    0x7 => Instruction::new_i_type(Opcode::CSRRCI, rd, rs1, csr),
+                    _ => return illegal(DecodeErrorKind::ReservedFunct3),
+                }
+            },
+            0x7B => match funct3 { // Custom-0
+                0x0 => Instruction::new_i_type(Opcode::HALT, 0, 0, 0),
+                _ => return illegal(DecodeErrorKind::ReservedFunct3),
+            },
+            _ => return illegal(DecodeErrorKind::UnknownOpcode),
+        };
+        Ok(instr)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DecodeErrorKind {
+    UnknownOpcode,       // Major opcode (bits 6:0) is not implemented
+    ReservedFunct3,      // funct3 not defined for this opcode
+    ReservedFunct7,      // funct7 (or imm[11:5] of a shift) not defined
+    ReservedEncoding,    // Fields that must be zero are not (SYSTEM)
+    MisalignedImmediate, // Branch/jump offset not a multiple of 4 (no C extension)
+}
+
+// A word that does not decode, with the raw bits kept for diagnostics
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DecodeError {
+    pub word: u32,
+    pub kind: DecodeErrorKind,
+}
+
+impl DecodeError {
+    pub fn opcode(&self) -> u32 {
+        self.word & 0x7F
+    }
+
+    pub fn funct3(&self) -> u32 {
+        (self.word >> 12) & 0x7
+    }
+
+    pub fn funct7(&self) -> u32 {
+        (self.word >> 25) & 0x7F
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "illegal instruction 0x{:08x}: ", self.word)?;
+        match self.kind {
+            DecodeErrorKind::UnknownOpcode => write!(f, "unknown opcode 0b{:07b}", self.opcode()),
+            DecodeErrorKind::ReservedFunct3 => {
+                write!(f, "reserved funct3 0b{:03b} for opcode 0b{:07b}", self.funct3(), self.opcode())
+            }
+            DecodeErrorKind::ReservedFunct7 => write!(
+                f,
+                "reserved funct7 0b{:07b} for opcode 0b{:07b} funct3 0b{:03b}",
+                self.funct7(),
+                self.opcode(),
+                self.funct3()
+            ),
+            DecodeErrorKind::ReservedEncoding => {
+                write!(f, "reserved encoding for opcode 0b{:07b}", self.opcode())
+            }
+            DecodeErrorKind::MisalignedImmediate => {
+                write!(f, "branch/jump offset is not a multiple of 4")
+            }
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+// Reasons an Instruction cannot be turned back into a 32-bit word
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EncodeError {
+    RegisterOutOfRange { field: &'static str, value: usize },
+    ImmediateOutOfRange { imm: i32, min: i32, max: i32 },
+    MisalignedImmediate { imm: i32, align: i32 },
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EncodeError::RegisterOutOfRange { field, value } => {
+                write!(f, "register {} = x{} is out of range (x0..x31)", field, value)
+            }
+            EncodeError::ImmediateOutOfRange { imm, min, max } => {
+                write!(f, "immediate {} is out of range [{}, {}]", imm, min, max)
+            }
+            EncodeError::MisalignedImmediate { imm, align } => {
+                write!(f, "immediate {} is not a multiple of {}", imm, align)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EncodeError {}
+
+fn check_reg(field: &'static str, value: usize) -> Result<u32, EncodeError> {
+    if value < 32 { Ok(value as u32) } else { Err(EncodeError::RegisterOutOfRange { field, value }) }
+}
+
+fn check_imm(imm: i32, min: i32, max: i32, align: i32) -> Result<u32, EncodeError> {
+    if imm < min || imm > max {
+        return Err(EncodeError::ImmediateOutOfRange { imm, min, max });
+    }
+    if imm % align != 0 {
+        return Err(EncodeError::MisalignedImmediate { imm, align });
+    }
+    Ok(imm as u32)
+}
+
+impl Instruction {
+    // Inverse of `decode`: for every word that decodes successfully,
+    // `Instruction::decode(w)?.encode() == Ok(w)`. The one exception is HALT,
+    // whose operand fields are don't-care and always encode as zero.
+    pub fn encode(&self) -> Result<u32, EncodeError> {
+        let rd = check_reg("rd", self.rd)?;
+        let rs1 = check_reg("rs1", self.rs1)?;
+        let rs2 = check_reg("rs2", self.rs2)?;
+
+        let r_type = |funct7: u32, funct3: u32, opcode: u32| {
+            Ok((funct7 << 25) | (rs2 << 20) | (rs1 << 15) | (funct3 << 12) | (rd << 7) | opcode)
+        };
+        let i_type = |funct3: u32, opcode: u32| -> Result<u32, EncodeError> {
+            let imm = check_imm(self.imm, -2048, 2047, 1)?;
+            Ok(((imm & 0xFFF) << 20) | (rs1 << 15) | (funct3 << 12) | (rd << 7) | opcode)
+        };
+        let shift = |funct7: u32, funct3: u32| -> Result<u32, EncodeError> {
+            let shamt = check_imm(self.imm, 0, 31, 1)?;
+            Ok((funct7 << 25) | (shamt << 20) | (rs1 << 15) | (funct3 << 12) | (rd << 7) | 0x13)
+        };
+        let s_type = |funct3: u32| -> Result<u32, EncodeError> {
+            let imm = check_imm(self.imm, -2048, 2047, 1)?;
+            Ok((((imm >> 5) & 0x7F) << 25) | (rs2 << 20) | (rs1 << 15) | (funct3 << 12)
+                | ((imm & 0x1F) << 7) | 0x23)
+        };
+        let b_type = |funct3: u32| -> Result<u32, EncodeError> {
+            let imm = check_imm(self.imm, -4096, 4092, 4)?;
+            Ok((((imm >> 12) & 1) << 31) | (((imm >> 5) & 0x3F) << 25) | (rs2 << 20) | (rs1 << 15)
+                | (funct3 << 12) | (((imm >> 1) & 0xF) << 8) | (((imm >> 11) & 1) << 7) | 0x63)
+        };
+        let csr_type = |funct3: u32| -> Result<u32, EncodeError> {
+            let csr = check_imm(self.imm, 0, 0xFFF, 1)?;
+            Ok((csr << 20) | (rs1 << 15) | (funct3 << 12) | (rd << 7) | 0x73)
+        };
+        let u_type = |opcode: u32| -> Result<u32, EncodeError> {
+            if self.imm & 0xFFF != 0 {
+                return Err(EncodeError::MisalignedImmediate { imm: self.imm, align: 4096 });
+            }
+            Ok((self.imm as u32) | (rd << 7) | opcode)
+        };
+
+        match self.opcode {
+            Opcode::ADD => r_type(0x00, 0x0, 0x33),
+            Opcode::SUB => r_type(0x20, 0x0, 0x33),
+            Opcode::SLL => r_type(0x00, 0x1, 0x33),
+            Opcode::SLT => r_type(0x00, 0x2, 0x33),
+            Opcode::SLTU => r_type(0x00, 0x3, 0x33),
+            Opcode::XOR => r_type(0x00, 0x4, 0x33),
+            Opcode::SRL => r_type(0x00, 0x5, 0x33),
+            Opcode::SRA => r_type(0x20, 0x5, 0x33),
+            Opcode::OR => r_type(0x00, 0x6, 0x33),
+            Opcode::AND => r_type(0x00, 0x7, 0x33),
+            Opcode::MUL => r_type(0x01, 0x0, 0x33),
+            Opcode::MULH => r_type(0x01, 0x1, 0x33),
+            Opcode::MULHSU => r_type(0x01, 0x2, 0x33),
+            Opcode::MULHU => r_type(0x01, 0x3, 0x33),
+            Opcode::DIV => r_type(0x01, 0x4, 0x33),
+            Opcode::DIVU => r_type(0x01, 0x5, 0x33),
+            Opcode::REM => r_type(0x01, 0x6, 0x33),
+            Opcode::REMU => r_type(0x01, 0x7, 0x33),
+            Opcode::ADDI => i_type(0x0, 0x13),
+            Opcode::SLTI => i_type(0x2, 0x13),
+            Opcode::SLTIU => i_type(0x3, 0x13),
+            Opcode::XORI => i_type(0x4, 0x13),
+            Opcode::ORI => i_type(0x6, 0x13),
+            Opcode::ANDI => i_type(0x7, 0x13),
+            Opcode::SLLI => shift(0x00, 0x1),
+            Opcode::SRLI => shift(0x00, 0x5),
+            Opcode::SRAI => shift(0x20, 0x5),
+            Opcode::LB => i_type(0x0, 0x03),
+            Opcode::LH => i_type(0x1, 0x03),
+            Opcode::LW => i_type(0x2, 0x03),
+            Opcode::LBU => i_type(0x4, 0x03),
+            Opcode::LHU => i_type(0x5, 0x03),
+            Opcode::SB => s_type(0x0),
+            Opcode::SH => s_type(0x1),
+            Opcode::SW => s_type(0x2),
+            Opcode::BEQ => b_type(0x0),
+            Opcode::BNE => b_type(0x1),
+            Opcode::BLT => b_type(0x4),
+            Opcode::BGE => b_type(0x5),
+            Opcode::BLTU => b_type(0x6),
+            Opcode::BGEU => b_type(0x7),
+            Opcode::JAL => {
+                let imm = check_imm(self.imm, -(1 << 20), (1 << 20) - 4, 4)?;
+                Ok((((imm >> 20) & 1) << 31) | (((imm >> 1) & 0x3FF) << 21) | (((imm >> 11) & 1) << 20)
+                    | (((imm >> 12) & 0xFF) << 12) | (rd << 7) | 0x6F)
+            }
+            Opcode::JALR => i_type(0x0, 0x67),
+            Opcode::LUI => u_type(0x37),
+            Opcode::AUIPC => u_type(0x17),
+            Opcode::FENCE => i_type(0x0, 0x0F),
+            Opcode::ECALL => Ok(0x0000_0073),
+            Opcode::EBREAK => Ok(0x0010_0073),
+            Opcode::MRET => Ok(0x3020_0073),
+            Opcode::CSRRW => csr_type(0x1),
+            Opcode::CSRRS => csr_type(0x2),
+            Opcode::CSRRC => csr_type(0x3),
+            Opcode::CSRRWI => csr_type(0x5),
+            Opcode::CSRRSI => csr_type(0x6),
+            Opcode::CSRRCI => csr_type(0x7),
+            Opcode::HALT => Ok(0x0000_007B),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const R_OPS: [Opcode; 18] = [
+        Opcode::ADD, Opcode::SUB, Opcode::SLL, Opcode::SLT, Opcode::SLTU, Opcode::XOR,
+        Opcode::SRL, Opcode::SRA, Opcode::OR, Opcode::AND, Opcode::MUL, Opcode::MULH,
+        Opcode::MULHSU, Opcode::MULHU, Opcode::DIV, Opcode::DIVU, Opcode::REM, Opcode::REMU,
+    ];
+    const I_OPS: [Opcode; 13] = [
+        Opcode::ADDI, Opcode::SLTI, Opcode::SLTIU, Opcode::XORI, Opcode::ORI, Opcode::ANDI,
+        Opcode::LB, Opcode::LH, Opcode::LW, Opcode::LBU, Opcode::LHU, Opcode::JALR, Opcode::FENCE,
+    ];
+    const SHIFT_OPS: [Opcode; 3] = [Opcode::SLLI, Opcode::SRLI, Opcode::SRAI];
+    const B_OPS: [Opcode; 6] = [Opcode::BEQ, Opcode::BNE, Opcode::BLT, Opcode::BGE, Opcode::BLTU, Opcode::BGEU];
+
+    fn assert_round_trip(instr: Instruction) {
+        let word = instr.encode().unwrap_or_else(|e| panic!("{:?}: {}", instr, e));
+        assert_eq!(Instruction::decode(word), Ok(instr), "word 0x{:08x}", word);
+    }
+
+    #[test]
+    fn test_round_trip_every_opcode() {
+        let regs = [0, 1, 15, 31];
+        for &op in &R_OPS {
+            for &(rd, rs1, rs2) in &[(0, 0, 0), (1, 2, 3), (31, 15, 31)] {
+                assert_round_trip(Instruction::new_r_type(op, rd, rs1, rs2));
+            }
+        }
+        for &op in &I_OPS {
+            for &imm in &[-2048, -1, 0, 1, 2047] {
+                for &r in &regs {
+                    assert_round_trip(Instruction::new_i_type(op, r, 31 - r, imm));
+                }
+            }
+        }
+        for &op in &SHIFT_OPS {
+            for &shamt in &[0, 1, 31] {
+                assert_round_trip(Instruction::new_i_type(op, 5, 6, shamt));
+            }
+        }
+        for &op in &[Opcode::SB, Opcode::SH, Opcode::SW] {
+            for &imm in &[-2048, -1, 0, 31, 32, 2047] {
+                assert_round_trip(Instruction::new_s_type(op, 2, 31, imm));
+            }
+        }
+        for &op in &B_OPS {
+            for &imm in &[-4096, -4, 0, 4, 2044, 2048, 4092] {
+                assert_round_trip(Instruction::new_b_type(op, 1, 2, imm));
+            }
+        }
+        for &imm in &[-(1 << 20), -4, 0, 4, 2048, 4096, (1 << 20) - 4] {
+            assert_round_trip(Instruction::new_j_type(Opcode::JAL, 1, imm));
+        }
+        for &op in &[Opcode::LUI, Opcode::AUIPC] {
+            for &imm in &[0, 0x1000, 0x7FFF_F000, i32::MIN, -4096] {
+                assert_round_trip(Instruction::new_u_type(op, 7, imm));
+            }
+        }
+        assert_round_trip(Instruction::new_i_type(Opcode::ECALL, 0, 0, 0));
+        assert_round_trip(Instruction::new_i_type(Opcode::EBREAK, 0, 0, 1));
+        assert_round_trip(Instruction::new_i_type(Opcode::MRET, 0, 0, 0x302));
+        for &op in &[Opcode::CSRRW, Opcode::CSRRS, Opcode::CSRRC, Opcode::CSRRWI, Opcode::CSRRSI, Opcode::CSRRCI] {
+            for &csr in &[0, 0x300, 0xB02, 0xFFF] {
+                assert_round_trip(Instruction::new_i_type(op, 3, 31, csr));
+            }
+        }
+        assert_round_trip(Instruction::new_i_type(Opcode::HALT, 0, 0, 0));
+    }
+
+    #[test]
+    fn test_encode_inverts_decode_for_random_words() {
+        // Every word that decodes to a known opcode must re-encode bit-exactly
+        let mut state: u32 = 0x1234_5678;
+        let mut decoded = 0;
+        for _ in 0..200_000 {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            // Force a valid major opcode half of the time to hit more formats
+            const MAJORS: [u32; 9] = [0x33, 0x13, 0x03, 0x23, 0x63, 0x6F, 0x67, 0x37, 0x73];
+            let major = MAJORS[(state as usize >> 7) % MAJORS.len()];
+            let word = if state & 0x8000_0000 != 0 { state } else { (state & !0x7F) | major };
+            if let Ok(instr) = Instruction::decode(word) {
+                if instr.opcode == Opcode::HALT {
+                    continue;
+                }
+                assert_eq!(instr.encode(), Ok(word), "{:?}", instr);
+                decoded += 1;
+            }
+        }
+        assert!(decoded > 10_000);
+    }
+
+    #[test]
+    fn test_encode_range_checks() {
+        assert_eq!(
+            Instruction::new_i_type(Opcode::ADDI, 1, 0, 2048).encode(),
+            Err(EncodeError::ImmediateOutOfRange { imm: 2048, min: -2048, max: 2047 })
+        );
+        assert_eq!(
+            Instruction::new_b_type(Opcode::BEQ, 1, 2, 6).encode(),
+            Err(EncodeError::MisalignedImmediate { imm: 6, align: 4 })
+        );
+        assert_eq!(
+            Instruction::new_r_type(Opcode::ADD, 32, 0, 0).encode(),
+            Err(EncodeError::RegisterOutOfRange { field: "rd", value: 32 })
+        );
+        assert_eq!(
+            Instruction::new_u_type(Opcode::LUI, 1, 0x123).encode(),
+            Err(EncodeError::MisalignedImmediate { imm: 0x123, align: 4096 })
+        );
+        assert!(Instruction::new_i_type(Opcode::SLLI, 1, 1, 32).encode().is_err());
+    }
+
+    #[test]
+    fn test_decode_errors_keep_raw_word() {
+        let cases = [
+            (0xFFFF_FFFF, DecodeErrorKind::UnknownOpcode),
+            (0x0000A063, DecodeErrorKind::ReservedFunct3),   // branch funct3=010
+            (0x00003003, DecodeErrorKind::ReservedFunct3),   // load funct3=011 (LD is RV64)
+            (0x40001033, DecodeErrorKind::ReservedFunct7),   // SUB encoding with funct3=001
+            (0x0200D093, DecodeErrorKind::ReservedFunct7),   // SRLI with shamt[5] set
+            (0x00200073, DecodeErrorKind::ReservedEncoding), // SYSTEM, not ECALL/EBREAK
+            (0x00000163, DecodeErrorKind::MisalignedImmediate), // beq x0, x0, +2
+        ];
+        for &(word, kind) in &cases {
+            assert_eq!(Instruction::decode(word), Err(DecodeError { word, kind }));
         }
+        let err = Instruction::decode(0x0000A063).unwrap_err();
+        assert_eq!(err.to_string(), "illegal instruction 0x0000a063: reserved funct3 0b010 for opcode 0b1100011");
     }
 }
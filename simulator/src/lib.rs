@@ -1,5 +1,22 @@
 pub mod core;
+pub mod pipeline;
+pub mod predictor;
+pub mod error;
+pub mod trap;
+pub mod csr;
 pub mod memory;
+pub mod bus;
+pub mod dma;
+pub mod ring_dma;
+pub mod irq;
+pub mod queue;
+pub mod cache;
+pub mod dram;
 pub mod isa;
 pub mod driver;
+pub mod ffi;
 pub mod kernels;
+pub mod disasm;
+pub mod hexfile;
+pub mod asm;
+pub mod builder;
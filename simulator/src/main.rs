@@ -1,9 +1,15 @@
-use simulator::driver::AcceleratorDriver;
-use simulator::isa::Instruction;
+use simulator::asm;
+use simulator::cache::{Cache, CacheConfig};
+use simulator::core::Core;
+use simulator::disasm::{self, DisasmOptions};
+use simulator::driver::{AcceleratorDriver, KernelArgs};
+use simulator::hexfile;
 use simulator::kernels;
+use simulator::isa::Instruction;
+use simulator::pipeline::{Forwarding, Pipeline};
+use simulator::predictor::{predictor_by_name, BranchUnit, Btb, PREDICTOR_NAMES};
 use std::env;
 use std::fs;
-use std::io::Write;
 
 fn main() {
     let args: Vec<String> = env::args().collect();
@@ -22,34 +28,218 @@ fn main() {
              let json_output = args.contains(&"--json".to_string());
 
              let kernel = kernels::get_particle_sim_kernel();
-             
-             // Initial State
-             let particles = 10;
-             // Init memory... (omitted for brevity, relies on driver defaults in full ver)
-             // Actually let's just run simple demo
-             // driver.copy_to_device(...)
-             
+
+             // Initial state: 10 particles at 0, 2, 4, ... (PARTICLE_SIM_ARGS base and count)
+             let positions: Vec<i32> = (0..10).map(|i| i * 2).collect();
+             if let Err(e) = driver.write_slice(0, &positions) {
+                 eprintln!("Failed to load particles: {}", e);
+                 std::process::exit(1);
+             }
+
              if !json_output {
                 println!("Running Particle Simulation for {} frames...", frames);
              }
-             
+
+             let mut json_frames = Vec::new();
              for f in 0..frames {
-                 let _ = driver.submit_kernel(kernel.clone());
-                 if !json_output {
-                    println!("Frame {} Complete. Cycles: {}", f, driver.get_perf_stats());
+                 let _ = driver.submit_kernel_with(kernel.clone(), &KernelArgs::new(&kernels::PARTICLE_SIM_ARGS));
+                 let positions = driver.read_slice::<i32>(0, 10).unwrap_or_default();
+                 if json_output {
+                    json_frames.push(format!("{{\"frame\": {}, \"particles\": {:?}}}", f, positions));
+                 } else {
+                    println!("Frame {} Complete. Cycles: {}. Positions: {:?}", f, driver.get_perf_stats(), positions);
                  }
              }
+             if json_output {
+                 println!("[{}]", json_frames.join(", "));
+             }
         },
         "benchmark" => {
             // Simplified Benchmark Mode for Python Script
+            // --pipeline uses the 5-stage timing model and also reports hazards;
+            // --predictor <name> [--btb <entries>] adds a branch predictor to it
+            let flag_value = |flag: &str| args.iter().position(|a| a == flag).and_then(|i| args.get(i + 1));
+            let branch_unit = match flag_value("--predictor") {
+                Some(name) => {
+                    let Some(predictor) = predictor_by_name(name) else {
+                        eprintln!("Unknown predictor '{}' (one of: {})", name, PREDICTOR_NAMES.join(", "));
+                        std::process::exit(1);
+                    };
+                    let btb = match flag_value("--btb") {
+                        None => None,
+                        Some(n) => match n.parse().map_err(|_| format!("invalid BTB size '{}'", n)).and_then(Btb::new) {
+                            Ok(btb) => Some(btb),
+                            Err(e) => {
+                                eprintln!("Error: {}", e);
+                                std::process::exit(1);
+                            }
+                        },
+                    };
+                    Some(BranchUnit::new(predictor, btb))
+                }
+                None => None,
+            };
+            // --caches adds the planned 16KB direct-mapped L1 I/D caches,
+            // --memory-hierarchy also puts a shared L2 and DRAM behind them
+            if args.contains(&"--memory-hierarchy".to_string()) {
+                driver.core = match Core::new(0).with_memory_hierarchy() {
+                    Ok(core) => core,
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        std::process::exit(1);
+                    }
+                };
+            } else if args.contains(&"--caches".to_string()) {
+                driver.core.icache = Some(Cache::new(CacheConfig::default()).unwrap());
+                driver.core.dcache = Some(Cache::new(CacheConfig::default()).unwrap());
+            }
+            let pipelined = args.contains(&"--pipeline".to_string()) || branch_unit.is_some();
+            if pipelined {
+                let mut pipeline = Pipeline::new();
+                pipeline.branch_unit = branch_unit;
+                driver.core.pipeline = Some(pipeline);
+            }
             let kernel = kernels::get_particle_sim_kernel();
-            match driver.submit_kernel(kernel) {
-                Ok(stats) => println!("CYCLES:{}", stats.core_cycles),
+            match driver.submit_kernel_with(kernel, &KernelArgs::new(&kernels::PARTICLE_SIM_ARGS)) {
+                Ok(stats) => {
+                    println!("CYCLES:{}", stats.core_cycles);
+                    if pipelined {
+                        println!("INSTRUCTIONS:{}", stats.instructions);
+                        println!("STALL_CYCLES:{}", stats.stall_cycles);
+                        println!("FLUSHES:{}", stats.flushes);
+                        println!("FLUSH_CYCLES:{}", stats.flush_cycles);
+                    }
+                    for (name, cache) in [("ICACHE", stats.icache), ("DCACHE", stats.dcache), ("L2", stats.l2)] {
+                        if let Some(c) = cache {
+                            println!(
+                                "{}: reads={} writes={} hits={} misses={} evictions={} writebacks={} hit_rate={:.3}",
+                                name, c.reads, c.writes, c.hits, c.misses, c.evictions, c.writebacks, c.hit_rate()
+                            );
+                        }
+                    }
+                    if let Some(d) = stats.dram {
+                        println!(
+                            "DRAM: reads={} writes={} row_hits={} row_misses={} row_conflicts={} busy_cycles={}",
+                            d.reads, d.writes, d.row_hits, d.row_misses, d.row_conflicts, d.busy_cycles
+                        );
+                    }
+                    if !stats.branches.is_empty() {
+                        println!("MISPREDICTIONS:{}", stats.mispredictions);
+                        for (pc, b) in &stats.branches {
+                            println!(
+                                "BRANCH:0x{:08x} executed={} taken={} mispredicted={} penalty_cycles={}",
+                                pc, b.executed, b.taken, b.mispredicted, b.penalty_cycles
+                            );
+                        }
+                    }
+                }
                 Err(e) => eprintln!("Error: {}", e),
             }
         },
+        "disasm" => {
+            // simulator disasm <file.hex> [--abi] [--base <addr>]
+            let Some(path) = args.get(2) else {
+                eprintln!("Usage: simulator disasm <file.hex> [--abi] [--base <addr>]");
+                return;
+            };
+            let base = match args.iter().position(|a| a == "--base").map(|i| args.get(i + 1)) {
+                None => 0,
+                Some(value) => match value.and_then(|v| u32::from_str_radix(v.trim_start_matches("0x"), 16).ok()) {
+                    Some(base) => base,
+                    None => {
+                        eprintln!("Error: --base expects a hex address");
+                        std::process::exit(1);
+                    }
+                },
+            };
+            let options = DisasmOptions { abi_names: args.contains(&"--abi".to_string()), labels: true };
+            match hexfile::read_hex_file(path) {
+                Ok(words) => print!("{}", disasm::disassemble_with(&words, base, &options)),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        },
+        "asm" => {
+            // simulator asm <input.s> [-o <output.bin|output.hex>]
+            // Without -o the hex words are printed to stdout.
+            let Some(input) = args.get(2) else {
+                eprintln!("Usage: simulator asm <input.s> [-o <output.bin|output.hex>]");
+                return;
+            };
+            let output = args.iter().position(|a| a == "-o").and_then(|i| args.get(i + 1));
+            let source = match fs::read_to_string(input) {
+                Ok(s) => s,
+                Err(e) => {
+                    eprintln!("Error: {}: {}", input, e);
+                    std::process::exit(1);
+                }
+            };
+            let program = match asm::assemble(&source) {
+                Ok(p) => p,
+                Err(e) => {
+                    eprintln!("{}: {}", input, e);
+                    std::process::exit(1);
+                }
+            };
+            let result = match output {
+                Some(path) if path.ends_with(".bin") => fs::write(path, &program.image),
+                Some(path) => fs::write(path, hexfile::format_hex(&program.words())),
+                None => {
+                    print!("{}", hexfile::format_hex(&program.words()));
+                    Ok(())
+                }
+            };
+            if let Err(e) = result {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        },
+        "forwarding" => {
+            // simulator forwarding [<file.hex>]
+            // Runs the kernel (default: particle kernel) under every forwarding
+            // option of the pipeline model and prints the cycle counts side by side.
+            let (kernel, launch_args) = match args.get(2) {
+                Some(path) => {
+                    let decoded = hexfile::read_hex_file(path).and_then(|words| {
+                        words.into_iter().map(Instruction::decode).collect::<Result<Vec<_>, _>>()
+                            .map_err(|e| e.to_string())
+                    });
+                    match decoded {
+                        Ok(kernel) => (kernel, KernelArgs::default()),
+                        Err(e) => {
+                            eprintln!("Error: {}", e);
+                            std::process::exit(1);
+                        }
+                    }
+                }
+                None => (kernels::get_particle_sim_kernel(), KernelArgs::new(&kernels::PARTICLE_SIM_ARGS)),
+            };
+            println!("{:<10} {:>8} {:>8} {:>8} {:>7} {:>8}", "forwarding", "cycles", "instrs", "stalls", "CPI", "speedup");
+            let mut baseline = None;
+            for forwarding in Forwarding::ALL {
+                let mut driver = AcceleratorDriver::new();
+                driver.core.pipeline = Some(Pipeline::with_forwarding(forwarding));
+                match driver.submit_kernel_with(kernel.clone(), &launch_args) {
+                    Ok(stats) => {
+                        let base = *baseline.get_or_insert(stats.core_cycles);
+                        println!(
+                            "{:<10} {:>8} {:>8} {:>8} {:>7.2} {:>7.2}x",
+                            forwarding.name(),
+                            stats.core_cycles,
+                            stats.instructions,
+                            stats.stall_cycles,
+                            stats.core_cycles as f64 / stats.instructions.max(1) as f64,
+                            base as f64 / stats.core_cycles as f64,
+                        );
+                    }
+                    Err(e) => eprintln!("{}: Error: {}", forwarding.name(), e),
+                }
+            }
+        },
         _ => {
-            println!("Usage: simulator [particles|benchmark]");
+            println!("Usage: simulator [particles|benchmark|disasm|asm|forwarding]");
         }
     }
 }
@@ -1,6 +1,8 @@
 
 // Memory Model with simulated latency
 
+use crate::error::{AccessKind, SimError};
+
 pub struct Memory {
     pub data: Vec<u8>,
     pub size: usize,
@@ -16,10 +18,38 @@ impl Memory {
         }
     }
 
-    pub fn read_word(&self, addr: usize) -> Result<u32, String> {
-        if addr + 4 > self.size {
-            return Err(format!("Memory read out of bounds: 0x{:08x}", addr));
+    // Alignment is the core's concern; memory only checks the bounds
+    fn check_bounds(&self, addr: usize, len: usize, kind: AccessKind) -> Result<(), SimError> {
+        if addr + len > self.size {
+            return Err(SimError::AccessFault { addr: addr as u32, kind });
         }
+        Ok(())
+    }
+
+    pub fn read_u8(&self, addr: usize) -> Result<u8, SimError> {
+        self.check_bounds(addr, 1, AccessKind::Read)?;
+        Ok(self.data[addr])
+    }
+
+    pub fn read_u16(&self, addr: usize) -> Result<u16, SimError> {
+        self.check_bounds(addr, 2, AccessKind::Read)?;
+        Ok(u16::from_le_bytes([self.data[addr], self.data[addr + 1]]))
+    }
+
+    pub fn write_u8(&mut self, addr: usize, value: u8) -> Result<(), SimError> {
+        self.check_bounds(addr, 1, AccessKind::Write)?;
+        self.data[addr] = value;
+        Ok(())
+    }
+
+    pub fn write_u16(&mut self, addr: usize, value: u16) -> Result<(), SimError> {
+        self.check_bounds(addr, 2, AccessKind::Write)?;
+        self.data[addr..addr + 2].copy_from_slice(&value.to_le_bytes());
+        Ok(())
+    }
+
+    pub fn read_word(&self, addr: usize) -> Result<u32, SimError> {
+        self.check_bounds(addr, 4, AccessKind::Read)?;
         let b0 = self.data[addr] as u32;
         let b1 = self.data[addr + 1] as u32;
         let b2 = self.data[addr + 2] as u32;
@@ -28,10 +58,8 @@ impl Memory {
         Ok(b0 | (b1 << 8) | (b2 << 16) | (b3 << 24))
     }
 
-    pub fn write_word(&mut self, addr: usize, value: u32) -> Result<(), String> {
-        if addr + 4 > self.size {
-            return Err(format!("Memory write out of bounds: 0x{:08x}", addr));
-        }
+    pub fn write_word(&mut self, addr: usize, value: u32) -> Result<(), SimError> {
+        self.check_bounds(addr, 4, AccessKind::Write)?;
         self.data[addr] = (value & 0xFF) as u8;
         self.data[addr + 1] = ((value >> 8) & 0xFF) as u8;
         self.data[addr + 2] = ((value >> 16) & 0xFF) as u8;
@@ -0,0 +1,304 @@
+
+// Pipeline Timing Model (ARCHITECTURE.md section 2)
+// Cycle-approximate model of the 5-stage in-order pipeline. The core still
+// executes one instruction per step; this model only decides *when* each
+// instruction occupies IF/ID/EX/MEM/WB, so it never changes results.
+//
+// - Each stage holds one instruction; an instruction enters a stage once the
+//   previous instruction has left it (in-order, no overtaking).
+// - Registers are read at the end of ID. Without forwarding a reader waits
+//   in ID until the cycle after the writer's WB (no same-cycle write/read).
+//   With forwarding (see Forwarding) the value can instead be bypassed into
+//   EX; a load result only exists after MEM, so load-use still stalls.
+// - Branches and jumps resolve in EX: a taken branch, JAL, JALR or MRET
+//   flushes IF and ID, so fetch resumes after the branch leaves EX. With a
+//   BranchUnit (predictor.rs) installed, branches and jumps follow its
+//   prediction instead and only pay for the redirect it reports.
+// - Multi-cycle M-extension ops occupy EX, memory latency occupies MEM and
+//   instruction cache misses occupy IF.
+
+use crate::isa::{Instruction, Opcode};
+use crate::predictor::{BranchUnit, Redirect};
+
+pub const IF: usize = 0;
+pub const ID: usize = 1;
+pub const EX: usize = 2;
+pub const MEM: usize = 3;
+pub const WB: usize = 4;
+pub const STAGES: usize = 5;
+
+// Bypass paths into the EX stage, for what-if studies of the v2.0 roadmap
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Forwarding {
+    #[default]
+    None,   // Interlock only (current RTL)
+    ExToEx, // EX/MEM latch -> EX: ALU results of the instruction in MEM
+    Full,   // EX/MEM and MEM/WB latches -> EX, including load data
+}
+
+impl Forwarding {
+    pub const ALL: [Forwarding; 3] = [Forwarding::None, Forwarding::ExToEx, Forwarding::Full];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Forwarding::None => "none",
+            Forwarding::ExToEx => "ex-ex",
+            Forwarding::Full => "ex+mem-ex",
+        }
+    }
+}
+
+// Timing of the last in-flight writer of a register
+#[derive(Debug, Clone, Copy, Default)]
+struct Producer {
+    mem: u64, // Cycle it entered MEM (result in the EX/MEM latch)
+    wb: u64,  // Cycle it entered WB (result in the MEM/WB latch)
+    load: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct Pipeline {
+    pub forwarding: Forwarding,
+    pub branch_unit: Option<BranchUnit>, // None: static predict-not-taken
+    prev: [u64; STAGES],    // Cycle the previous instruction entered each stage
+    fetch_at: u64,          // Earliest cycle for the next fetch (redirects)
+    producers: [Producer; 32],
+    pub stall_cycles: u64,  // ID bubbles inserted for RAW hazards
+    pub flushes: u64,       // Taken branches/jumps that flushed IF and ID
+    pub flush_cycles: u64,  // Fetch cycles lost to those flushes
+    pub instructions: u64,
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_forwarding(forwarding: Forwarding) -> Self {
+        Pipeline { forwarding, ..Self::default() }
+    }
+
+    pub fn with_branch_unit(mut self, branch_unit: BranchUnit) -> Self {
+        self.branch_unit = Some(branch_unit);
+        self
+    }
+
+    // Drop all in-flight state, statistics and predictor training, as on a
+    // core reset; the configuration is kept
+    pub fn reset(&mut self) {
+        let mut branch_unit = self.branch_unit.take();
+        if let Some(unit) = branch_unit.as_mut() {
+            unit.reset();
+        }
+        *self = Pipeline { forwarding: self.forwarding, branch_unit, ..Self::default() };
+    }
+
+    // Earliest cycle >= `cycle` at which register `reg` can be consumed in EX
+    fn operand_ready(&self, reg: usize, cycle: u64) -> u64 {
+        let p = self.producers[reg];
+        let from_regfile = cycle.max(p.wb + 2);
+        match self.forwarding {
+            Forwarding::None => from_regfile,
+            // Only while the producer sits in MEM; once it moves on to WB the
+            // reader has to wait for the register file.
+            Forwarding::ExToEx if !p.load && cycle < p.wb => cycle.max(p.mem),
+            Forwarding::ExToEx => from_regfile,
+            // The register file is written in WB, so no gap after the bypass
+            Forwarding::Full => cycle.max(if p.load { p.wb } else { p.mem }),
+        }
+    }
+
+    // Cycle at which the last issued instruction leaves WB
+    pub fn cycles(&self) -> u64 {
+        if self.instructions == 0 { 0 } else { self.prev[WB] + 1 }
+    }
+
+    // A trap is taken precisely: the pipeline drains before the handler is fetched
+    pub fn drain(&mut self) {
+        self.fetch_at = self.fetch_at.max(self.cycles());
+    }
+
+    // Schedules one retired instruction. `fetch` are the extra cycles spent
+    // in IF, `extra` the remaining cycles beyond the base cycle the functional
+    // model charged (M-extension latency for EX, memory latency and
+    // misalignment penalties for MEM). `next_pc` is where
+    // the instruction at `pc` actually continued. Returns the total cycle
+    // count so far.
+    pub fn issue(&mut self, pc: u32, instr: &Instruction, fetch: u64, extra: u64, next_pc: u32) -> u64 {
+        let mut duration = [1u64; STAGES];
+        duration[IF] += fetch;
+        if instr.is_load() || instr.is_store() {
+            duration[MEM] += extra;
+        } else {
+            duration[EX] += extra;
+        }
+
+        let prev = self.prev;
+        let mut t = [0u64; STAGES];
+        t[IF] = if self.instructions == 0 { self.fetch_at } else { prev[ID].max(self.fetch_at) };
+        t[ID] = (t[IF] + duration[IF]).max(prev[EX]);
+
+        // Leaving ID requires EX to be free and every source to be written back
+        let ex_free = (t[ID] + duration[ID]).max(prev[MEM]);
+        let sources = instr.source_regs();
+        let mut ready = ex_free;
+        loop {
+            // Bypass windows are not monotonic, so settle on a cycle that
+            // suits every source operand at once
+            let next = sources.iter().flatten().fold(ready, |c, &r| c.max(self.operand_ready(r, ready)));
+            if next == ready {
+                break;
+            }
+            ready = next;
+        }
+        t[EX] = ready;
+        self.stall_cycles += t[EX] - ex_free;
+
+        t[MEM] = (t[EX] + duration[EX]).max(prev[WB]);
+        t[WB] = (t[MEM] + duration[MEM]).max(prev[WB] + 1);
+
+        if let Some(rd) = instr.dest_reg() {
+            self.producers[rd] = Producer { mem: t[MEM], wb: t[WB], load: instr.is_load() };
+        }
+
+        let predicted = self.branch_unit.as_mut().and_then(|unit| unit.resolve(pc, instr, next_pc));
+        let redirect = predicted.unwrap_or_else(|| {
+            let is_jump = matches!(instr.opcode, Opcode::JAL | Opcode::JALR | Opcode::MRET);
+            if is_jump || next_pc != pc.wrapping_add(4) { Redirect::Execute } else { Redirect::None }
+        });
+        let resume = match redirect {
+            Redirect::None => None,
+            Redirect::Decode => Some(t[ID] + duration[ID]),
+            Redirect::Execute => Some(t[EX] + duration[EX]),
+        };
+        if let Some(resume) = resume {
+            // Without the redirect the next instruction would have entered ID
+            // once this one left it; a branch stalled in ID hides part of it
+            let lost = (resume + duration[IF]).saturating_sub(t[EX]);
+            self.flushes += 1;
+            self.flush_cycles += lost;
+            self.fetch_at = resume;
+            if let (Some(unit), Some(_)) = (self.branch_unit.as_mut(), predicted) {
+                unit.add_penalty(pc, lost);
+            }
+        }
+
+        self.prev = t;
+        self.instructions += 1;
+        self.cycles()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addi(rd: usize, rs1: usize) -> Instruction {
+        Instruction::new_i_type(Opcode::ADDI, rd, rs1, 1)
+    }
+
+    #[test]
+    fn test_independent_instructions_have_cpi_one() {
+        let mut p = Pipeline::new();
+        for i in 1..=4 {
+            p.issue(0, &addi(i, 0), 0, 0, 4);
+        }
+        // 4 cycles to fill the pipeline, then one instruction per cycle
+        assert_eq!(p.cycles(), 4 + 4);
+        assert_eq!(p.stall_cycles, 0);
+    }
+
+    #[test]
+    fn test_raw_hazard_stalls_until_writeback() {
+        let mut p = Pipeline::new();
+        p.issue(0, &addi(1, 0), 0, 0, 4);
+        p.issue(0, &addi(2, 1), 0, 0, 4);
+        assert_eq!(p.stall_cycles, 3);
+        assert_eq!(p.cycles(), 5 + 1 + 3);
+    }
+
+    #[test]
+    fn test_taken_branch_flushes_two_cycles() {
+        let mut p = Pipeline::new();
+        let beq = Instruction::new_b_type(Opcode::BEQ, 0, 0, 8);
+        p.issue(0, &beq, 0, 0, 8);
+        p.issue(0, &addi(1, 0), 0, 0, 4);
+        assert_eq!((p.flushes, p.flush_cycles), (1, 2));
+        assert_eq!(p.cycles(), 5 + 1 + 2);
+
+        // A not-taken branch costs nothing extra
+        let mut p = Pipeline::new();
+        p.issue(0, &beq, 0, 0, 4);
+        p.issue(0, &addi(1, 0), 0, 0, 4);
+        assert_eq!((p.flushes, p.cycles()), (0, 6));
+    }
+
+    #[test]
+    fn test_multicycle_ex_and_mem_block_later_instructions() {
+        let mut p = Pipeline::new();
+        p.issue(0, &Instruction::new_r_type(Opcode::DIV, 1, 2, 3), 0, 10, 4);
+        p.issue(0, &addi(4, 0), 0, 0, 4);
+        assert_eq!(p.cycles(), 5 + 10 + 1);
+
+        let mut p = Pipeline::new();
+        p.issue(0, &Instruction::new_i_type(Opcode::LW, 1, 0, 0), 0, 3, 4);
+        p.issue(0, &addi(4, 0), 0, 0, 4);
+        assert_eq!(p.cycles(), 5 + 3 + 1);
+    }
+
+    #[test]
+    fn test_forwarding_removes_alu_stalls() {
+        let lw = Instruction::new_i_type(Opcode::LW, 1, 0, 0);
+        let stalls = |forwarding: Forwarding, producer: &Instruction, distance: usize| {
+            let mut p = Pipeline::with_forwarding(forwarding);
+            p.issue(0, producer, 0, 0, 4);
+            for i in 1..distance {
+                p.issue(0, &addi(10 + i, 0), 0, 0, 4);
+            }
+            p.issue(0, &addi(2, 1), 0, 0, 4);
+            p.stall_cycles
+        };
+        let alu = addi(1, 0);
+        assert_eq!([1, 2, 3].map(|d| stalls(Forwarding::None, &alu, d)), [3, 2, 1]);
+        // EX->EX only covers the immediately following instruction
+        assert_eq!([1, 2, 3].map(|d| stalls(Forwarding::ExToEx, &alu, d)), [0, 2, 1]);
+        assert_eq!([1, 2, 3].map(|d| stalls(Forwarding::Full, &alu, d)), [0, 0, 0]);
+        // Load-use keeps one bubble even with full forwarding
+        assert_eq!(stalls(Forwarding::ExToEx, &lw, 1), 3);
+        assert_eq!(stalls(Forwarding::Full, &lw, 1), 1);
+        assert_eq!(stalls(Forwarding::Full, &lw, 2), 0);
+    }
+
+    #[test]
+    fn test_reset_keeps_forwarding() {
+        let mut p = Pipeline::with_forwarding(Forwarding::Full);
+        p.issue(0, &addi(1, 0), 0, 0, 4);
+        p.reset();
+        assert_eq!((p.forwarding, p.instructions), (Forwarding::Full, 0));
+    }
+
+    #[test]
+    fn test_branch_unit_replaces_static_flush() {
+        use crate::predictor::{Btb, Btfn};
+        // A backward loop branch at 0x8, taken three times then falling through
+        let bne = Instruction::new_b_type(Opcode::BNE, 1, 0, -8);
+        let run = |mut p: Pipeline| {
+            for i in 0..4 {
+                p.issue(0x0, &addi(2, 0), 0, 0, 0x4);
+                p.issue(0x4, &addi(3, 0), 0, 0, 0x8);
+                p.issue(0x8, &bne, 0, 0, if i < 3 { 0x0 } else { 0xC });
+            }
+            p
+        };
+        let p = run(Pipeline::new());
+        assert_eq!((p.flushes, p.flush_cycles), (3, 6));
+
+        let p = run(Pipeline::new().with_branch_unit(BranchUnit::new(Box::new(Btfn), Some(Btb::new(4).unwrap()))));
+        // First taken iteration computes the target in ID, then the BTB hits;
+        // the final fall-through is mispredicted
+        assert_eq!((p.flushes, p.flush_cycles), (2, 3));
+        let unit = p.branch_unit.as_ref().unwrap();
+        assert_eq!(unit.stats[&0x8].mispredicted, 1);
+        assert_eq!(unit.stats[&0x8].penalty_cycles, 3);
+    }
+}
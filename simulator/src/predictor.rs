@@ -0,0 +1,345 @@
+
+// Branch Prediction Models (ARCHITECTURE.md section 4, v2.0 roadmap)
+// Direction predictors and a branch target buffer for the pipeline timing
+// model. They only affect timing, never which path the core executes.
+//
+// Front-end timing with a BranchUnit installed:
+// - predicted taken with a BTB hit on the right target: redirected in IF, free
+// - predicted taken without a usable BTB entry: JAL/branch targets are
+//   computed in ID, 1 cycle
+// - wrong direction, or a JALR without the right BTB target: resolved in EX,
+//   2 cycles (a misprediction)
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::isa::{Instruction, Opcode};
+
+pub trait BranchPredictor: fmt::Debug {
+    fn name(&self) -> &'static str;
+    // Direction prediction for a conditional branch at `pc`
+    fn predict(&self, pc: u32, instr: &Instruction) -> bool;
+    fn update(&mut self, pc: u32, instr: &Instruction, taken: bool);
+    // Forget all learned state
+    fn reset(&mut self);
+}
+
+fn table_index(pc: u32, len: usize) -> usize {
+    (pc as usize >> 2) & (len - 1)
+}
+
+fn check_table_size(entries: usize) -> Result<(), String> {
+    if !entries.is_power_of_two() {
+        return Err(format!("predictor table size must be a power of two, got {}", entries));
+    }
+    Ok(())
+}
+
+// Static predict-not-taken (what the current RTL does)
+#[derive(Debug, Clone, Default)]
+pub struct AlwaysNotTaken;
+
+impl BranchPredictor for AlwaysNotTaken {
+    fn name(&self) -> &'static str { "not-taken" }
+    fn predict(&self, _pc: u32, _instr: &Instruction) -> bool { false }
+    fn update(&mut self, _pc: u32, _instr: &Instruction, _taken: bool) {}
+    fn reset(&mut self) {}
+}
+
+// Static backward-taken/forward-not-taken, favours loops
+#[derive(Debug, Clone, Default)]
+pub struct Btfn;
+
+impl BranchPredictor for Btfn {
+    fn name(&self) -> &'static str { "btfn" }
+    fn predict(&self, _pc: u32, instr: &Instruction) -> bool { instr.imm < 0 }
+    fn update(&mut self, _pc: u32, _instr: &Instruction, _taken: bool) {}
+    fn reset(&mut self) {}
+}
+
+// One last-outcome bit per entry
+#[derive(Debug, Clone)]
+pub struct OneBit {
+    table: Vec<bool>,
+}
+
+impl OneBit {
+    pub fn new(entries: usize) -> Result<Self, String> {
+        check_table_size(entries)?;
+        Ok(OneBit { table: vec![false; entries] })
+    }
+}
+
+impl BranchPredictor for OneBit {
+    fn name(&self) -> &'static str { "1bit" }
+    fn predict(&self, pc: u32, _instr: &Instruction) -> bool {
+        self.table[table_index(pc, self.table.len())]
+    }
+    fn update(&mut self, pc: u32, _instr: &Instruction, taken: bool) {
+        let idx = table_index(pc, self.table.len());
+        self.table[idx] = taken;
+    }
+    fn reset(&mut self) {
+        self.table.fill(false);
+    }
+}
+
+// 2-bit saturating counters (0-1 predict not taken, 2-3 taken), start weakly not taken
+fn counter_update(counter: &mut u8, taken: bool) {
+    *counter = if taken { (*counter + 1).min(3) } else { counter.saturating_sub(1) };
+}
+
+#[derive(Debug, Clone)]
+pub struct TwoBit {
+    table: Vec<u8>,
+}
+
+impl TwoBit {
+    pub fn new(entries: usize) -> Result<Self, String> {
+        check_table_size(entries)?;
+        Ok(TwoBit { table: vec![1; entries] })
+    }
+}
+
+impl BranchPredictor for TwoBit {
+    fn name(&self) -> &'static str { "2bit" }
+    fn predict(&self, pc: u32, _instr: &Instruction) -> bool {
+        self.table[table_index(pc, self.table.len())] >= 2
+    }
+    fn update(&mut self, pc: u32, _instr: &Instruction, taken: bool) {
+        let idx = table_index(pc, self.table.len());
+        counter_update(&mut self.table[idx], taken);
+    }
+    fn reset(&mut self) {
+        self.table.fill(1);
+    }
+}
+
+// 2-bit counters indexed by PC xor global branch history
+pub const GSHARE_MAX_HISTORY_BITS: u32 = 20; // 1M-entry table
+#[derive(Debug, Clone)]
+pub struct Gshare {
+    history_bits: u32,
+    history: u32,
+    table: Vec<u8>,
+}
+
+impl Gshare {
+    pub fn new(history_bits: u32) -> Result<Self, String> {
+        if history_bits > GSHARE_MAX_HISTORY_BITS {
+            return Err(format!("gshare history is at most {} bits, got {}", GSHARE_MAX_HISTORY_BITS, history_bits));
+        }
+        Ok(Gshare { history_bits, history: 0, table: vec![1; 1 << history_bits] })
+    }
+
+    fn index(&self, pc: u32) -> usize {
+        table_index(pc, self.table.len()) ^ self.history as usize
+    }
+}
+
+impl BranchPredictor for Gshare {
+    fn name(&self) -> &'static str { "gshare" }
+    fn predict(&self, pc: u32, _instr: &Instruction) -> bool {
+        self.table[self.index(pc)] >= 2
+    }
+    fn update(&mut self, pc: u32, _instr: &Instruction, taken: bool) {
+        let idx = self.index(pc);
+        counter_update(&mut self.table[idx], taken);
+        let mask = (1u32 << self.history_bits) - 1;
+        self.history = ((self.history << 1) | taken as u32) & mask;
+    }
+    fn reset(&mut self) {
+        self.history = 0;
+        self.table.fill(1);
+    }
+}
+
+// Predictors selectable by name, with default table sizes
+pub const PREDICTOR_NAMES: [&str; 5] = ["not-taken", "btfn", "1bit", "2bit", "gshare"];
+
+pub fn predictor_by_name(name: &str) -> Option<Box<dyn BranchPredictor>> {
+    Some(match name {
+        "not-taken" => Box::new(AlwaysNotTaken),
+        "btfn" => Box::new(Btfn),
+        "1bit" => Box::new(OneBit::new(256).unwrap()),
+        "2bit" => Box::new(TwoBit::new(256).unwrap()),
+        "gshare" => Box::new(Gshare::new(8).unwrap()),
+        _ => return None,
+    })
+}
+
+// Direct-mapped branch target buffer, tagged with the full PC
+#[derive(Debug, Clone)]
+pub struct Btb {
+    entries: Vec<Option<(u32, u32)>>, // (pc, target)
+}
+
+impl Btb {
+    pub fn new(entries: usize) -> Result<Self, String> {
+        check_table_size(entries)?;
+        Ok(Btb { entries: vec![None; entries] })
+    }
+
+    pub fn lookup(&self, pc: u32) -> Option<u32> {
+        match self.entries[table_index(pc, self.entries.len())] {
+            Some((tag, target)) if tag == pc => Some(target),
+            _ => None,
+        }
+    }
+
+    pub fn insert(&mut self, pc: u32, target: u32) {
+        let idx = table_index(pc, self.entries.len());
+        self.entries[idx] = Some((pc, target));
+    }
+
+    pub fn reset(&mut self) {
+        self.entries.fill(None);
+    }
+}
+
+// Where the front end learns the correct next PC of a control-flow instruction
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Redirect {
+    None,    // Fetch already followed the right path
+    Decode,  // Target computed in ID (1 cycle)
+    Execute, // Resolved in EX (2 cycles, a misprediction)
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BranchStats {
+    pub executed: u64,
+    pub taken: u64,
+    pub mispredicted: u64,
+    pub penalty_cycles: u64,
+}
+
+#[derive(Debug)]
+pub struct BranchUnit {
+    pub predictor: Box<dyn BranchPredictor>,
+    pub btb: Option<Btb>,
+    pub stats: BTreeMap<u32, BranchStats>, // Keyed by branch PC
+}
+
+impl BranchUnit {
+    pub fn new(predictor: Box<dyn BranchPredictor>, btb: Option<Btb>) -> Self {
+        BranchUnit { predictor, btb, stats: BTreeMap::new() }
+    }
+
+    pub fn reset(&mut self) {
+        self.predictor.reset();
+        if let Some(btb) = self.btb.as_mut() {
+            btb.reset();
+        }
+        self.stats.clear();
+    }
+
+    pub fn mispredictions(&self) -> u64 {
+        self.stats.values().map(|s| s.mispredicted).sum()
+    }
+
+    // Predicts, trains and records one retired instruction. Returns None for
+    // anything but branches, JAL and JALR.
+    pub fn resolve(&mut self, pc: u32, instr: &Instruction, next_pc: u32) -> Option<Redirect> {
+        let conditional = matches!(
+            instr.opcode,
+            Opcode::BEQ | Opcode::BNE | Opcode::BLT | Opcode::BGE | Opcode::BLTU | Opcode::BGEU
+        );
+        if !conditional && !matches!(instr.opcode, Opcode::JAL | Opcode::JALR) {
+            return None;
+        }
+        let taken = !conditional || next_pc != pc.wrapping_add(4);
+        let predicted_taken = !conditional || self.predictor.predict(pc, instr);
+        let btb_target = self.btb.as_ref().and_then(|btb| btb.lookup(pc));
+
+        let redirect = if taken != predicted_taken {
+            Redirect::Execute
+        } else if !taken || btb_target == Some(next_pc) {
+            Redirect::None
+        } else if instr.opcode == Opcode::JALR {
+            Redirect::Execute // Register target is only known in EX
+        } else {
+            Redirect::Decode
+        };
+
+        if conditional {
+            self.predictor.update(pc, instr, taken);
+        }
+        if taken {
+            if let Some(btb) = self.btb.as_mut() {
+                btb.insert(pc, next_pc);
+            }
+        }
+        let stats = self.stats.entry(pc).or_default();
+        stats.executed += 1;
+        stats.taken += taken as u64;
+        stats.mispredicted += (redirect == Redirect::Execute) as u64;
+        Some(redirect)
+    }
+
+    pub fn add_penalty(&mut self, pc: u32, cycles: u64) {
+        self.stats.entry(pc).or_default().penalty_cycles += cycles;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bne_back() -> Instruction {
+        Instruction::new_b_type(Opcode::BNE, 1, 0, -8)
+    }
+
+    // Mispredictions of a loop branch taken `trips - 1` times, then not taken, repeated
+    fn loop_mispredictions(predictor: Box<dyn BranchPredictor>, trips: usize, repeats: usize) -> u64 {
+        let mut unit = BranchUnit::new(predictor, Some(Btb::new(16).unwrap()));
+        for _ in 0..repeats {
+            for i in 0..trips {
+                let next_pc = if i + 1 < trips { 0x18 } else { 0x24 };
+                unit.resolve(0x20, &bne_back(), next_pc);
+            }
+        }
+        unit.mispredictions()
+    }
+
+    #[test]
+    fn test_predictors_on_a_loop() {
+        let mispredictions: Vec<u64> = PREDICTOR_NAMES
+            .iter()
+            .map(|name| loop_mispredictions(predictor_by_name(name).unwrap(), 10, 4))
+            .collect();
+        // not-taken misses every taken iteration; btfn and 1bit miss the exit
+        // (1bit also the re-entry); 2bit only the exits after warm-up; gshare
+        // warms up a counter per history pattern, and 8 bits of history are
+        // shorter than the 10-iteration period
+        assert_eq!(mispredictions, [36, 4, 8, 5, 23]);
+    }
+
+    #[test]
+    fn test_btb_and_decode_redirects() {
+        let jal = Instruction::new_j_type(Opcode::JAL, 0, 0x40);
+        let jalr = Instruction::new_i_type(Opcode::JALR, 0, 1, 0);
+
+        let mut unit = BranchUnit::new(Box::new(AlwaysNotTaken), None);
+        assert_eq!(unit.resolve(0, &jal, 0x40), Some(Redirect::Decode));
+        assert_eq!(unit.resolve(0, &jalr, 0x80), Some(Redirect::Execute));
+        assert_eq!(unit.resolve(0, &Instruction::new_i_type(Opcode::ADDI, 1, 0, 1), 4), None);
+
+        let mut unit = BranchUnit::new(Box::new(AlwaysNotTaken), Some(Btb::new(4).unwrap()));
+        assert_eq!(unit.resolve(0, &jal, 0x40), Some(Redirect::Decode));
+        assert_eq!(unit.resolve(0, &jal, 0x40), Some(Redirect::None));
+        assert_eq!(unit.resolve(8, &jalr, 0x80), Some(Redirect::Execute));
+        assert_eq!(unit.resolve(8, &jalr, 0x80), Some(Redirect::None));
+        assert_eq!(unit.resolve(8, &jalr, 0x90), Some(Redirect::Execute)); // Stale target
+        assert_eq!(unit.stats[&8], BranchStats { executed: 3, taken: 3, mispredicted: 2, penalty_cycles: 0 });
+    }
+
+    #[test]
+    fn test_table_sizes_are_checked() {
+        assert_eq!(OneBit::new(100).unwrap_err(), "predictor table size must be a power of two, got 100");
+        assert!(TwoBit::new(0).is_err());
+        assert!(Btb::new(3).is_err());
+        assert!(Btb::new(64).is_ok());
+        assert!(Gshare::new(GSHARE_MAX_HISTORY_BITS).is_ok());
+        assert_eq!(Gshare::new(64).unwrap_err(), "gshare history is at most 20 bits, got 64");
+    }
+}
@@ -0,0 +1,132 @@
+
+// Command Queues (HARDWARE_INTERFACE_v0.md section 6)
+// Streams of device work, executed in order within each queue without a
+// host round trip between commands. The device runs one command at a time;
+// when several queues have work, the driver's Arbitration policy picks the
+// next queue. A Barrier holds its queue until every command submitted
+// earlier, to any queue, has completed. Timestamps are device clock cycles.
+
+use std::collections::VecDeque;
+
+use crate::driver::PerfStats;
+use crate::error::SimError;
+use crate::isa::Instruction;
+
+pub type QueueId = usize;
+
+// Host-visible marker, signaled when a queue reaches it. Only an
+// EventTable hands them out.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Event(usize); // Index into its EventTable
+
+// Device cycle each event was signaled at, None until then
+#[derive(Debug, Default)]
+pub struct EventTable {
+    signaled: Vec<Option<u64>>,
+}
+
+impl EventTable {
+    pub fn create(&mut self) -> Event {
+        self.signaled.push(None);
+        Event(self.signaled.len() - 1)
+    }
+
+    // Fails for an event created by another driver
+    pub fn timestamp(&self, event: Event) -> Result<Option<u64>, SimError> {
+        self.signaled.get(event.0).copied().ok_or(SimError::UnknownEvent)
+    }
+
+    pub fn signal(&mut self, event: Event, cycle: u64) -> Result<(), SimError> {
+        let slot = self.signaled.get_mut(event.0).ok_or(SimError::UnknownEvent)?;
+        *slot = Some(cycle);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Command {
+    CopyToDevice { addr: u32, data: Vec<u8> },
+    CopyFromDevice { addr: u32, len: usize },
+    LaunchKernel(Vec<Instruction>),
+    Barrier,
+    SignalEvent(Event),
+}
+
+impl Command {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Command::CopyToDevice { .. } => "copy-to-device",
+            Command::CopyFromDevice { .. } => "copy-from-device",
+            Command::LaunchKernel(_) => "launch-kernel",
+            Command::Barrier => "barrier",
+            Command::SignalEvent(_) => "signal-event",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Arbitration {
+    Fifo,       // Oldest submitted command first, across all queues
+    RoundRobin, // One command per queue in turn
+    Priority,   // Highest queue priority first, FIFO among equals
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Timestamps {
+    pub submitted: u64,
+    pub started: u64,
+    pub completed: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Output {
+    None,
+    Data(Vec<u8>),          // CopyFromDevice
+    Kernel(Box<PerfStats>), // LaunchKernel
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Completion {
+    pub seq: u64, // Global submission order
+    pub command: &'static str,
+    pub times: Timestamps,
+    pub result: Result<Output, SimError>,
+}
+
+struct Pending {
+    seq: u64,
+    submitted: u64,
+    command: Command,
+}
+
+pub struct CommandQueue {
+    pub priority: u32,
+    pub completions: Vec<Completion>,
+    // First failure; every later command in the queue is skipped with it
+    pub error: Option<SimError>,
+    pending: VecDeque<Pending>,
+}
+
+impl CommandQueue {
+    pub fn new(priority: u32) -> Self {
+        CommandQueue { priority, completions: Vec::new(), error: None, pending: VecDeque::new() }
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    pub fn push(&mut self, seq: u64, submitted: u64, command: Command) {
+        self.pending.push_back(Pending { seq, submitted, command });
+    }
+
+    // Sequence number and command at the head of the queue
+    pub fn head(&self) -> Option<(u64, &Command)> {
+        self.pending.front().map(|p| (p.seq, &p.command))
+    }
+
+    // Removes the head, returning (seq, submitted, command)
+    pub fn pop(&mut self) -> Option<(u64, u64, Command)> {
+        self.pending.pop_front().map(|p| (p.seq, p.submitted, p.command))
+    }
+}
@@ -0,0 +1,394 @@
+
+// Descriptor Ring DMA (HARDWARE_INTERFACE_future.md section 1, v2.0)
+// The host fills 16-byte descriptors in a circular buffer in host memory and
+// advances TAIL_PTR; the engine fetches them one at a time over PCIe, runs up
+// to max_in_flight copies side by side and retires them in ring order,
+// advancing HEAD_PTR and writing the descriptor flags back. Completion
+// interrupts are coalesced: IRQ_DMA_COMPLETE fires after COALESCE_COUNT
+// completions, or COALESCE_TIMEOUT cycles after the oldest unreported one.
+//
+// Descriptor layout (little-endian words):
+//   +0x0 SRC (host address)  +0x4 DST (SRAM address)
+//   +0x8 LEN (in words)      +0xC FLAGS (written back by the engine)
+
+use std::collections::VecDeque;
+
+use crate::bus::Device;
+use crate::dma::{HostMemory, DMA_STATUS_BUSY, DMA_STATUS_ERROR};
+use crate::error::SimError;
+use crate::irq::IRQ_DMA_COMPLETE;
+use crate::memory::Memory;
+
+pub const RING_DMA_BASE: u32 = 0x5000_0000;
+pub const RING_DMA_REGS_SIZE: u32 = 0x24;
+
+// Register offsets
+pub const RING_BASE: u32 = 0x00; // Host address of descriptor 0
+pub const RING_SIZE: u32 = 0x04; // Entries, a power of two; writing it rewinds the pointers
+pub const DMA_TAIL_PTR: u32 = 0x08; // Next slot the host will fill
+pub const DMA_HEAD_PTR: u32 = 0x0C; // Next descriptor to complete (RO)
+pub const RING_CONTROL: u32 = 0x10;
+pub const RING_STATUS: u32 = 0x14; // DMA_STATUS_BUSY / DMA_STATUS_ERROR (RO)
+pub const COALESCE_COUNT: u32 = 0x18;
+pub const COALESCE_TIMEOUT: u32 = 0x1C; // Cycles, 0 = no timeout
+pub const RING_COMPLETED: u32 = 0x20; // Descriptors retired since reset (RO)
+
+// RING_CONTROL bits
+pub const RING_CTRL_ENABLE: u32 = 1 << 0;
+pub const RING_CTRL_INT_EN: u32 = 1 << 1;
+
+pub const DESCRIPTOR_SIZE: u32 = 16;
+
+// Descriptor FLAGS bits
+pub const DESC_DONE: u32 = 1 << 0;
+pub const DESC_ERROR: u32 = 1 << 1;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RingDmaConfig {
+    pub fetch_cycles: u64, // PCIe read of one descriptor
+    pub setup_cycles: u64,
+    pub cycles_per_word: u64,
+    pub max_in_flight: usize,
+}
+
+impl Default for RingDmaConfig {
+    fn default() -> Self {
+        RingDmaConfig { fetch_cycles: 20, setup_cycles: 10, cycles_per_word: 1, max_in_flight: 4 }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Transfer {
+    index: u32,
+    src: u32,
+    dst: u32,
+    len: u32,
+    remaining: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RingDmaStats {
+    pub descriptors: u64,
+    pub interrupts: u64,
+    pub peak_in_flight: usize,
+}
+
+pub struct RingDma {
+    pub config: RingDmaConfig,
+    pub host: HostMemory,
+    pub stats: RingDmaStats,
+    base: u32,
+    size: u32,
+    tail: u32,
+    head: u32,
+    next_fetch: u32,
+    ctrl: u32,
+    error: bool,
+    coalesce_count: u32,
+    coalesce_timeout: u32,
+    fetching: Option<u64>, // Cycles left on the descriptor being fetched
+    in_flight: VecDeque<Transfer>,
+    unreported: u32, // Completions not yet signalled
+    waiting: u64,    // Cycles since the oldest unreported completion
+}
+
+impl RingDma {
+    pub fn new(config: RingDmaConfig, host: HostMemory) -> Self {
+        RingDma {
+            config,
+            host,
+            stats: RingDmaStats::default(),
+            base: 0,
+            size: 0,
+            tail: 0,
+            head: 0,
+            next_fetch: 0,
+            ctrl: 0,
+            error: false,
+            coalesce_count: 1,
+            coalesce_timeout: 0,
+            fetching: None,
+            in_flight: VecDeque::new(),
+            unreported: 0,
+            waiting: 0,
+        }
+    }
+
+    // Posted descriptors only count while the engine is enabled to fetch them
+    fn busy(&self) -> bool {
+        let pending = self.ctrl & RING_CTRL_ENABLE != 0 && self.next_fetch != self.tail;
+        self.fetching.is_some() || !self.in_flight.is_empty() || pending
+    }
+
+    fn wrap(&self, index: u32) -> u32 {
+        (index + 1) & self.size.wrapping_sub(1)
+    }
+
+    // Widened before multiplying: a 2^31-entry ring spans 32GB. fetch() checks
+    // the result against host memory.
+    fn descriptor_addr(&self, index: u32) -> usize {
+        self.base as usize + index as usize * DESCRIPTOR_SIZE as usize
+    }
+
+    // Reads the descriptor at next_fetch into a new in-flight transfer. A
+    // descriptor outside host memory stops the engine with ERROR set.
+    fn fetch(&mut self) {
+        let addr = self.descriptor_addr(self.next_fetch);
+        let host = self.host.borrow();
+        let words: Result<Vec<u32>, SimError> = (0..3).map(|i| host.read_word(addr + i * 4)).collect();
+        drop(host);
+        let Ok(words) = words else {
+            self.error = true;
+            self.ctrl &= !RING_CTRL_ENABLE;
+            return;
+        };
+        let c = self.config;
+        self.in_flight.push_back(Transfer {
+            index: self.next_fetch,
+            src: words[0],
+            dst: words[1],
+            len: words[2],
+            remaining: c.setup_cycles + words[2] as u64 * c.cycles_per_word,
+        });
+        self.stats.peak_in_flight = self.stats.peak_in_flight.max(self.in_flight.len());
+        self.next_fetch = self.wrap(self.next_fetch);
+    }
+
+    // Copies the data and writes the descriptor flags back
+    fn retire(&mut self, t: Transfer, ram: &mut Memory) {
+        let mut host = self.host.borrow_mut();
+        let bytes = t.len as usize * 4;
+        let (src, dst) = (t.src as usize, t.dst as usize);
+        let flags = if src + bytes > host.size || dst + bytes > ram.size {
+            self.error = true;
+            DESC_DONE | DESC_ERROR
+        } else {
+            ram.data[dst..dst + bytes].copy_from_slice(&host.data[src..src + bytes]);
+            DESC_DONE
+        };
+        let _ = host.write_word(self.descriptor_addr(t.index) + 12, flags);
+        drop(host);
+        self.head = self.wrap(self.head);
+        self.stats.descriptors += 1;
+        self.unreported += 1;
+    }
+
+    fn cycle(&mut self, ram: &mut Memory) -> u32 {
+        if self.unreported > 0 {
+            self.waiting += 1;
+        }
+
+        for t in self.in_flight.iter_mut() {
+            t.remaining = t.remaining.saturating_sub(1);
+        }
+        while self.in_flight.front().is_some_and(|t| t.remaining == 0) {
+            let t = self.in_flight.pop_front().unwrap();
+            self.retire(t, ram);
+        }
+
+        let enabled = self.ctrl & RING_CTRL_ENABLE != 0;
+        if self.fetching.is_none()
+            && enabled
+            && self.next_fetch != self.tail
+            && self.in_flight.len() < self.config.max_in_flight
+        {
+            self.fetching = Some(self.config.fetch_cycles);
+        }
+        if let Some(left) = self.fetching {
+            if left <= 1 {
+                self.fetching = None;
+                self.fetch();
+            } else {
+                self.fetching = Some(left - 1);
+            }
+        }
+
+        if self.unreported == 0 {
+            return 0;
+        }
+        let count_reached = self.unreported >= self.coalesce_count.max(1);
+        let timed_out = self.coalesce_timeout > 0 && self.waiting >= self.coalesce_timeout as u64;
+        if !count_reached && !timed_out {
+            return 0;
+        }
+        self.unreported = 0;
+        self.waiting = 0;
+        if self.ctrl & RING_CTRL_INT_EN == 0 {
+            return 0;
+        }
+        self.stats.interrupts += 1;
+        IRQ_DMA_COMPLETE
+    }
+}
+
+impl Device for RingDma {
+    fn name(&self) -> &str {
+        "ring-dma"
+    }
+
+    fn read(&mut self, offset: u32, _size: u32) -> Result<u32, SimError> {
+        Ok(match offset & !3 {
+            RING_BASE => self.base,
+            RING_SIZE => self.size,
+            DMA_TAIL_PTR => self.tail,
+            DMA_HEAD_PTR => self.head,
+            RING_CONTROL => self.ctrl,
+            RING_STATUS => {
+                (if self.busy() { DMA_STATUS_BUSY } else { 0 }) | (if self.error { DMA_STATUS_ERROR } else { 0 })
+            }
+            COALESCE_COUNT => self.coalesce_count,
+            COALESCE_TIMEOUT => self.coalesce_timeout,
+            _ => self.stats.descriptors as u32,
+        })
+    }
+
+    fn write(&mut self, offset: u32, _size: u32, value: u32) -> Result<(), SimError> {
+        match offset & !3 {
+            RING_BASE => self.base = value,
+            RING_SIZE if value.is_power_of_two() => {
+                self.size = value;
+                self.tail = 0;
+                self.head = 0;
+                self.next_fetch = 0;
+            }
+            DMA_TAIL_PTR if self.size != 0 => self.tail = value & (self.size - 1),
+            RING_CONTROL => {
+                if value & RING_CTRL_ENABLE != 0 && self.ctrl & RING_CTRL_ENABLE == 0 {
+                    self.error = false;
+                }
+                self.ctrl = value;
+            }
+            COALESCE_COUNT => self.coalesce_count = value,
+            COALESCE_TIMEOUT => self.coalesce_timeout = value,
+            _ => {} // Read-only, or no valid ring configured
+        }
+        Ok(())
+    }
+
+    fn tick(&mut self, cycles: u64, ram: &mut Memory) -> u32 {
+        (0..cycles).fold(0, |irq, _| irq | self.cycle(ram))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::{Bus, SystemBus};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    const RING: u32 = 0x800;
+
+    fn system(config: RingDmaConfig) -> (SystemBus, HostMemory) {
+        let host = Rc::new(RefCell::new(Memory::new(0x1000, 0)));
+        let mut bus = SystemBus::new(Memory::new(0x400, 0));
+        bus.attach(RING_DMA_BASE, RING_DMA_REGS_SIZE, Box::new(RingDma::new(config, host.clone()))).unwrap();
+        bus.write(RING_DMA_BASE + RING_BASE, 4, RING).unwrap();
+        bus.write(RING_DMA_BASE + RING_SIZE, 4, 8).unwrap();
+        (bus, host)
+    }
+
+    fn post(host: &HostMemory, index: u32, src: u32, dst: u32, len: u32) {
+        let mut host = host.borrow_mut();
+        let addr = (RING + index * DESCRIPTOR_SIZE) as usize;
+        for (i, word) in [src, dst, len, 0].into_iter().enumerate() {
+            host.write_word(addr + i * 4, word).unwrap();
+        }
+    }
+
+    fn reg(bus: &mut SystemBus, offset: u32) -> u32 {
+        bus.read(RING_DMA_BASE + offset, 4).unwrap()
+    }
+
+    // Ticks until the engine is idle, returning (cycles, interrupts raised)
+    fn drain(bus: &mut SystemBus) -> (u64, u32) {
+        let (mut cycles, mut irqs) = (0, 0);
+        while reg(bus, RING_STATUS) & DMA_STATUS_BUSY != 0 {
+            irqs += (bus.tick(1) != 0) as u32;
+            cycles += 1;
+        }
+        (cycles, irqs)
+    }
+
+    #[test]
+    fn test_transfers_overlap_and_retire_in_order() {
+        let config = RingDmaConfig { fetch_cycles: 5, setup_cycles: 2, cycles_per_word: 1, max_in_flight: 4 };
+        let (mut bus, host) = system(config);
+        for i in 0..4 {
+            host.borrow_mut().write_word(i as usize * 4, 0x100 + i).unwrap();
+            post(&host, i, i * 4, 0x10 + i * 4, 1);
+        }
+        bus.write(RING_DMA_BASE + RING_CONTROL, 4, RING_CTRL_ENABLE | RING_CTRL_INT_EN).unwrap();
+        bus.write(RING_DMA_BASE + DMA_TAIL_PTR, 4, 4).unwrap();
+
+        // Fetches are serialised (4 * 5), the last copy then takes 3 cycles
+        assert_eq!(drain(&mut bus), (23, 4));
+        assert_eq!(reg(&mut bus, DMA_HEAD_PTR), 4);
+        assert_eq!(reg(&mut bus, RING_COMPLETED), 4);
+        for i in 0..4 {
+            assert_eq!(bus.ram.read_word(0x10 + i * 4).unwrap(), 0x100 + i as u32);
+            assert_eq!(host.borrow().read_word((RING + i as u32 * 16 + 12) as usize).unwrap(), DESC_DONE);
+        }
+    }
+
+    #[test]
+    fn test_interrupt_coalescing() {
+        let config = RingDmaConfig { fetch_cycles: 1, setup_cycles: 1, cycles_per_word: 1, max_in_flight: 2 };
+        let (mut bus, host) = system(config);
+        for i in 0..7 {
+            post(&host, i, 0, 0x100, 4);
+        }
+        bus.write(RING_DMA_BASE + COALESCE_COUNT, 4, 3).unwrap();
+        bus.write(RING_DMA_BASE + COALESCE_TIMEOUT, 4, 50).unwrap();
+        bus.write(RING_DMA_BASE + RING_CONTROL, 4, RING_CTRL_ENABLE | RING_CTRL_INT_EN).unwrap();
+        bus.write(RING_DMA_BASE + DMA_TAIL_PTR, 4, 7).unwrap();
+
+        // Two full batches of three; the seventh completion waits for the timeout
+        assert_eq!(drain(&mut bus).1, 2);
+        let mut timeout = 0;
+        while bus.tick(1) == 0 {
+            timeout += 1;
+        }
+        assert_eq!(timeout, 49);
+    }
+
+    #[test]
+    fn test_bad_descriptor_sets_error() {
+        let (mut bus, host) = system(RingDmaConfig::default());
+        post(&host, 0, 0, 0x3FC, 2); // Runs past the end of SRAM
+        bus.write(RING_DMA_BASE + RING_CONTROL, 4, RING_CTRL_ENABLE).unwrap();
+        bus.write(RING_DMA_BASE + DMA_TAIL_PTR, 4, 1).unwrap();
+        assert_eq!(drain(&mut bus).1, 0); // INT_EN clear
+        assert_eq!(reg(&mut bus, RING_STATUS), DMA_STATUS_ERROR);
+        assert_eq!(host.borrow().read_word((RING + 12) as usize).unwrap(), DESC_DONE | DESC_ERROR);
+    }
+
+    #[test]
+    fn test_descriptor_outside_host_memory_stops_the_engine() {
+        let (mut bus, _host) = system(RingDmaConfig::default());
+        bus.write(RING_DMA_BASE + RING_BASE, 4, 0xFF8).unwrap(); // Second word past the end
+        bus.write(RING_DMA_BASE + RING_CONTROL, 4, RING_CTRL_ENABLE).unwrap();
+        bus.write(RING_DMA_BASE + DMA_TAIL_PTR, 4, 2).unwrap();
+        drain(&mut bus);
+        assert_eq!(reg(&mut bus, RING_STATUS), DMA_STATUS_ERROR);
+        assert_eq!(reg(&mut bus, RING_CONTROL) & RING_CTRL_ENABLE, 0);
+        assert_eq!(reg(&mut bus, RING_COMPLETED), 0);
+    }
+
+    #[test]
+    fn test_largest_ring_size() {
+        let host = Rc::new(RefCell::new(Memory::new(0x1000, 0)));
+        let mut ram = Memory::new(0x400, 0);
+        let mut dma = RingDma::new(RingDmaConfig::default(), host);
+        dma.write(RING_SIZE, 4, 0x8000_0000).unwrap();
+        dma.write(RING_BASE, 4, RING).unwrap();
+        assert_eq!(dma.read(RING_SIZE, 4), Ok(0x8000_0000));
+        assert_eq!(dma.descriptor_addr(0x7FFF_FFFF), RING as usize + 0x7FFF_FFFF * 16);
+
+        // The last slot lies far outside host memory
+        dma.next_fetch = 0x7FFF_FFFF;
+        dma.write(RING_CONTROL, 4, RING_CTRL_ENABLE).unwrap();
+        dma.tick(RingDmaConfig::default().fetch_cycles, &mut ram);
+        assert_eq!(dma.read(RING_STATUS, 4), Ok(DMA_STATUS_ERROR));
+    }
+}
@@ -0,0 +1,88 @@
+
+// Trap / Exception Model (ISA_REFERENCE.md section 3.5)
+// A fault never leaves the core half-executed: the faulting instruction has
+// no architectural effect. If the kernel installed a handler (mtvec != 0) the
+// core enters it with mepc/mcause/mtval set; otherwise the PC stays on the
+// faulting instruction and the core halts with the cause recorded so the
+// driver can report it through DMA_STATUS[ERROR].
+
+use crate::error::{AccessKind, SimError};
+
+// Codes follow the RISC-V mcause exception numbering
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TrapCause {
+    InstructionAddressMisaligned,
+    InstructionAccessFault,
+    IllegalInstruction,
+    Breakpoint,
+    LoadAddressMisaligned,
+    LoadAccessFault,
+    StoreAddressMisaligned,
+    StoreAccessFault,
+    EnvironmentCall,
+    DivideByZero, // Hansen-specific, only with Core::trap_on_divide_by_zero
+}
+
+impl TrapCause {
+    pub fn code(&self) -> u32 {
+        match self {
+            TrapCause::InstructionAddressMisaligned => 0,
+            TrapCause::InstructionAccessFault => 1,
+            TrapCause::IllegalInstruction => 2,
+            TrapCause::Breakpoint => 3,
+            TrapCause::LoadAddressMisaligned => 4,
+            TrapCause::LoadAccessFault => 5,
+            TrapCause::StoreAddressMisaligned => 6,
+            TrapCause::StoreAccessFault => 7,
+            TrapCause::EnvironmentCall => 11, // ECALL from M-mode
+            TrapCause::DivideByZero => 24,    // First custom-use cause
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Trap {
+    pub cause: TrapCause,
+    pub pc: u32,   // Address of the faulting instruction
+    pub tval: u32, // Faulting address, or the raw word for illegal instructions
+}
+
+impl Trap {
+    // Translates a core fault into the trap it raises. Errors that are not
+    // caused by the executing kernel (watchdog, DMA) return None.
+    pub fn from_error(error: &SimError, pc: u32) -> Option<Trap> {
+        let (cause, tval) = match *error {
+            SimError::IllegalInstruction { error, .. } => (TrapCause::IllegalInstruction, error.word),
+            SimError::IllegalCsrAccess { word, .. } => (TrapCause::IllegalInstruction, word),
+            SimError::MisalignedAccess { addr, kind } => (
+                match kind {
+                    AccessKind::Fetch => TrapCause::InstructionAddressMisaligned,
+                    AccessKind::Read => TrapCause::LoadAddressMisaligned,
+                    AccessKind::Write => TrapCause::StoreAddressMisaligned,
+                },
+                addr,
+            ),
+            SimError::AccessFault { addr, kind } => (
+                match kind {
+                    AccessKind::Fetch => TrapCause::InstructionAccessFault,
+                    AccessKind::Read => TrapCause::LoadAccessFault,
+                    AccessKind::Write => TrapCause::StoreAccessFault,
+                },
+                addr,
+            ),
+            SimError::PcOutOfBounds { pc } => (TrapCause::InstructionAccessFault, pc),
+            SimError::DivideByZero { .. } => (TrapCause::DivideByZero, 0),
+            SimError::EnvironmentCall { .. } => (TrapCause::EnvironmentCall, 0),
+            SimError::Breakpoint { pc } => (TrapCause::Breakpoint, pc),
+            SimError::WatchdogTimeout { .. }
+            | SimError::WaitTimeout { .. }
+            | SimError::DeviceBusy
+            | SimError::FenceRetired
+            | SimError::UnknownQueue { .. }
+            | SimError::UnknownEvent
+            | SimError::DmaOutOfBounds { .. }
+            | SimError::StackOutOfBounds { .. } => return None,
+        };
+        Some(Trap { cause, pc, tval })
+    }
+}